@@ -0,0 +1,179 @@
+//! # ferris-mapcheck
+//!
+//! Checks .map files, and maps made with Tiled (.tmx and .json), for errors and warnings without opening a window.
+//!
+//! Usage: `ferris-mapcheck [FILE OR DIRECTORY]...`
+//!
+//! Every `.map`, `.tmx` and `.json` file in a directory is checked. With no arguments `./resources/maps/` is checked.
+//! The music and the background images the maps use are looked for in the resources directory
+//! the maps directory is in, and so are the kinds of enemies in `enemies.toml`, the ones the game
+//! ships with if there is none. Exits with a non zero status if any of the maps contains an error.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::exit,
+};
+
+use call_of_ferris::level::{Level, Roster, LEVEL_EXTENSIONS};
+
+/// Collect the map files to check from the command line arguments.
+fn collect_maps(args: Vec<String>) -> io::Result<Vec<PathBuf>> {
+    let mut paths = args.into_iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("./resources/maps/"));
+    }
+
+    let mut maps = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            let mut found = vec![];
+
+            for entry in fs::read_dir(&path)? {
+                let entry = entry?.path();
+
+                if entry
+                    .extension()
+                    .is_some_and(|ext| LEVEL_EXTENSIONS.iter().any(|known| ext == *known))
+                {
+                    found.push(entry);
+                }
+            }
+
+            found.sort();
+            maps.extend(found);
+        } else {
+            maps.push(path);
+        }
+    }
+
+    Ok(maps)
+}
+
+/// The resources directory next to the maps directory the map is in.
+fn resources_of(map: &Path) -> &Path {
+    map.parent()
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// The kinds of enemies in the resources directory of the map, the ones of the game if it has
+/// no `enemies.toml`.
+fn roster_of(map: &Path) -> Result<Roster, String> {
+    let path = resources_of(map).join("enemies.toml");
+
+    match fs::read_to_string(&path) {
+        Ok(source) => Roster::parse(&source).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(_) => Ok(Roster::default()),
+    }
+}
+
+/// The music and the background the level asks for that are not in the resources directory next
+/// to the maps directory, like the game expects them.
+fn missing_assets(map: &Path, level: &Level) -> Vec<String> {
+    let resources = resources_of(map);
+
+    let assets = [
+        ("music", "audio", &level.tuning.music),
+        ("background", "images", &level.tuning.background),
+    ];
+
+    assets
+        .iter()
+        .filter_map(|(name, folder, file)| Some((name, folder, file.as_ref()?)))
+        .filter(|(_, folder, file)| !resources.join(folder).join(file).is_file())
+        .map(|(name, folder, file)| {
+            format!(
+                "the {} `{}` is not in {}",
+                name,
+                file,
+                resources.join(folder).display()
+            )
+        })
+        .collect()
+}
+
+fn main() {
+    let maps = collect_maps(env::args().skip(1).collect()).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        exit(2);
+    });
+
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    for map in &maps {
+        let source = match fs::read_to_string(map) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}: error: {}", map.display(), e);
+                errors += 1;
+
+                continue;
+            }
+        };
+
+        let roster = match roster_of(map) {
+            Ok(roster) => roster,
+            Err(e) => {
+                eprintln!("{}: error: {}", map.display(), e);
+                errors += 1;
+
+                continue;
+            }
+        };
+
+        let extension = map.extension().unwrap_or_default().to_string_lossy();
+
+        match Level::from_source(&extension, &source, &roster) {
+            Ok(level) => {
+                for missing in missing_assets(map, &level) {
+                    eprintln!("{}: error: {}", map.display(), missing);
+                    errors += 1;
+                }
+
+                for warning in level.lint() {
+                    match warning.position {
+                        Some((line, column)) => println!(
+                            "{}:{}:{}: warning: {}",
+                            map.display(),
+                            line,
+                            column,
+                            warning.message
+                        ),
+                        None => println!("{}: warning: {}", map.display(), warning.message),
+                    }
+
+                    warnings += 1;
+                }
+            }
+            Err(e) => {
+                if e.line == 0 {
+                    eprintln!("{}: error: {}", map.display(), e.message);
+                } else {
+                    eprintln!(
+                        "{}:{}:{}: error: {}",
+                        map.display(),
+                        e.line,
+                        e.column,
+                        e.message
+                    );
+                }
+                errors += 1;
+            }
+        }
+    }
+
+    println!(
+        "Checked {} map(s): {} error(s), {} warning(s)",
+        maps.len(),
+        errors,
+        warnings
+    );
+
+    if errors > 0 {
+        exit(1);
+    }
+}
@@ -0,0 +1,66 @@
+//! How far the player jumps, shared by the game, [`Level::lint`](super::Level::lint) and the
+//! level generator so that they all agree on what can be jumped over.
+
+/// The gravity of the physics world, unless a level changes it with `.gravity`.
+pub const GRAVITY: f32 = 300.0;
+
+/// How long every step of the physics world is, in seconds.
+pub const PHYSICS_STEP: f32 = 1.0 / 60.0;
+
+/// How much velocity the player loses over time.
+pub const PLAYER_DAMPING: f32 = 1.0;
+
+/// The upward velocity a jump starts the player with.
+pub const JUMP_SPEED: f32 = 300.0;
+
+/// Horizontal velocity added every step the player moves.
+pub const SHIFT_JUICE: f32 = 10.0;
+
+/// The longest a jump is followed for, in seconds. With very little gravity the player barely
+/// falls back down, so a jump that gets high enough and is still in the air after that long
+/// is taken to float over anything.
+const MAX_AIR_TIME: f32 = 30.0;
+
+/// How far to the right the player gets with a single jump from a standstill, with the
+/// provided gravity, before falling back down to `rise` above where it jumped from. `None` if
+/// the jump does not get that high.
+pub fn jump_reach(rise: f32, gravity: f32) -> Option<f32> {
+    // Without gravity pulling it down the player floats on for as long as it wants.
+    if gravity <= 0.0 {
+        return Some(f32::INFINITY);
+    }
+
+    let damping = 1.0 / (1.0 + PHYSICS_STEP * PLAYER_DAMPING);
+
+    // Y grows downwards like in the world.
+    let (mut x, mut y) = (0.0, 0.0);
+    let (mut velocity_x, mut velocity_y) = (0.0, -JUMP_SPEED);
+    let mut apex: f32 = 0.0;
+
+    for _ in 0..(MAX_AIR_TIME / PHYSICS_STEP) as u32 {
+        // What the player does before every physics step, then what the step does.
+        velocity_x += SHIFT_JUICE;
+
+        velocity_y += gravity * PHYSICS_STEP;
+
+        velocity_x *= damping;
+        velocity_y *= damping;
+
+        x += velocity_x * PHYSICS_STEP;
+        y += velocity_y * PHYSICS_STEP;
+
+        apex = apex.min(y);
+
+        if velocity_y > 0.0 {
+            if apex > -rise {
+                return None;
+            }
+
+            if y >= -rise {
+                return Some(x);
+            }
+        }
+    }
+
+    Some(f32::INFINITY).filter(|_| apex <= -rise)
+}
@@ -0,0 +1,76 @@
+use super::{jump_reach, Level, MapWarning, PlatformMotion, GRAVITY};
+
+impl Level {
+    /// Look for things that are allowed in a .map file but most likely a mistake. The gaps and
+    /// the climbs between the tiles are checked against a single jump, see [`jump_reach`].
+    pub fn lint(&self) -> Vec<MapWarning> {
+        let mut warnings = vec![];
+        let gravity = self.tuning.gravity.unwrap_or(GRAVITY);
+
+        if self.enemies.is_empty() {
+            warnings.push(MapWarning {
+                position: None,
+                message: String::from(
+                    "the level has no enemies (`8`), it is won as soon as it starts",
+                ),
+            });
+        }
+
+        if self.end.is_none() {
+            warnings.push(MapWarning {
+                position: None,
+                message: String::from("the level has no `.end` quote for the win screen"),
+            });
+        }
+
+        for pair in self.tiles.windows(2) {
+            let (left_type, left) = &pair[0];
+            let (right_type, right) = &pair[1];
+
+            let left_edge = left.position.x + left_type.width() / 2.0;
+            let right_edge = right.position.x - right_type.width() / 2.0;
+            let gap = right_edge - left_edge;
+            // Y grows downwards, a tile higher up than the one before it has to be jumped up to.
+            let rise = left.position.y - right.position.y;
+            let reach = jump_reach(rise, gravity);
+
+            if reach.is_some_and(|reach| gap <= reach) {
+                continue;
+            }
+
+            // A platform that stops by in the gap carries the player over it.
+            let bridged = self.platforms.iter().any(|(motion, spawn)| {
+                let mut stops = vec![spawn.position.x];
+
+                if let PlatformMotion::PingPong(path) | PlatformMotion::Loop(path) = motion {
+                    stops.extend(path.points.iter().map(|point| spawn.position.x + point.x));
+                }
+
+                stops.into_iter().any(|x| x > left_edge && x < right_edge)
+            });
+
+            if bridged {
+                continue;
+            }
+
+            let message = match reach {
+                Some(reach) => format!(
+                    "the gap before the tile at X {} is {} wide, the player can only jump over {} there",
+                    right.position.x, gap, reach.floor()
+                ),
+                None => format!(
+                    "the tile at X {} is {} higher than the one before it, the player cannot jump that high",
+                    right.position.x, rise
+                ),
+            };
+
+            warnings.push(MapWarning {
+                // Tiles from a Tiled map have no line, the X position tells which tile it is.
+                position: Some((right.line, right.column)).filter(|&(line, _)| line > 0),
+                message,
+            });
+        }
+
+        warnings
+    }
+}
@@ -0,0 +1,566 @@
+//! Context free parsing and checking of the .map files made only for Call of Ferris.
+//!
+//! # Map
+//! `[` => Create left tile \
+//! `-` => Create center tile \
+//! `]` => Create right tile \
+//! `_` => Increase draw x by 100.0 \
+//! `8` => Push a tile with a enemy, that behaves as the last `.enemy` directive says. The other
+//! kinds of enemies have their own glyph, see [`Roster`] \
+//! `4` => Create a tile with the player \
+//! `*` => Create a tile with a barrel \
+//! `=` => Create a one-way tile, that can be jumped through from below \
+//! `~` => Create an ice tile, with no grip \
+//! `o` => Create a bouncy tile \
+//! `^` => Create a spike tile, that hurts the player \
+//! `>` => Create a moving platform, that follows the path of the last `.path` directive \
+//! `v` => Create a falling platform, that falls once the player stands on it
+//!
+//! # Setter Syntax
+//! `.comment` => A comment \
+//! `.using_weapon` => Set the current weapon \
+//! `.end` => The end quote displayed on the win screen \
+//! `.height` => Set the height, in rows of tiles, of the terrain on the following lines \
+//! `.path` => Set the path of the moving platforms on the following lines, see [`PlatformMotion`] \
+//! `.enemy` => Set how the enemies on the following lines behave, see [`EnemyBehaviour`]
+//!
+//! # Tuning
+//! These directives change how a level plays without recompiling the game, see [`Tuning`]: \
+//! `.gravity 300` => The gravity of the physics world \
+//! `.player_ammo 10` => The ammo the player starts with \
+//! `.player_health 100` => The health the player starts with \
+//! `.music <file>` => A sound from `resources/audio` played in a loop during the level \
+//! `.background <image>` => An image from `resources/images` drawn behind the level \
+//! `.double_jump on` => Let the player jump once more in the air, `off` by default
+//!
+//! # Terrain
+//! The glyph lines are laid out one after another from left to right. Each line is a segment
+//! that sits on the height set by the last `.height` directive (0 by default). A positive height
+//! raises the terrain and a negative one lowers it, so a climb is a few segments with an
+//! increasing height while a pit is a segment with a lower one:
+//!
+//! ```text
+//! [-4---]_
+//! .height 2
+//! [--8--]_
+//! .height -1
+//! [--*--]
+//! ```
+//!
+//! # Tiled
+//! Maps made with the [Tiled](https://www.mapeditor.org/) editor can be used as well, saved as
+//! .tmx or exported as .json, with CSV tile layers and embedded tilesets:
+//!
+//! * Every tile of a tile layer is a ground tile. The class (type in older Tiled versions) of
+//!   a tile in the tileset picks its [`TileType`]: `left`, `center`, `right`, `one_way`, `ice`,
+//!   `bouncy` or `spikes`, `center` if it has none. The map tiles should be 64x64 like the ground tiles.
+//! * The objects of the object layers with the class (type in older Tiled versions) `player`, `enemy` and `barrel`
+//!   spawn them at their centre. The `kind` property of an enemy is the name of its kind in the
+//!   [`Roster`], the first one if it has none. The ones with the class `moving_platform` and `falling_platform`
+//!   place a platform, the `path` property of a moving platform is written like the arguments
+//!   of the `.path` directive. The `behaviour` property of an enemy is written like the
+//!   arguments of the `.enemy` directive.
+//! * The `end`, `using_weapon` and [tuning](#tuning) properties of the map, or of any object,
+//!   do what the directives with the same name do.
+//!
+//! # Errors
+//! Parsing returns a [`MapError`] with the line and the column of the first problem found:
+//! unknown glyphs and directives, unknown weapons, a missing or duplicate player, platforms
+//! with unbalanced `[` and `]` and Tiled maps without tiles. Things that are allowed but most
+//! likely a mistake are reported as a [`MapWarning`] by [`Level::lint`]. Errors in .tmx maps
+//! point at the XML element that caused them. The .json maps have no such positions, their
+//! errors have a line of 0 and name the object, tile or layer that caused them instead, like the errors about the whole map.
+
+mod jump;
+mod lint;
+mod parser;
+mod roster;
+mod source;
+#[cfg(test)]
+mod tests;
+mod tiled;
+
+pub use jump::*;
+pub use roster::*;
+pub use source::*;
+
+use std::{error::Error, fmt};
+
+use rapier2d::na;
+use serde::Deserialize;
+
+/// The weapons a level can start with using `.using_weapon`.
+pub const WEAPONS: [&str; 2] = ["Turbofish Gun", "Grappling Gun"];
+
+/// The file extensions a level can be loaded from, in the order they are looked for.
+pub const LEVEL_EXTENSIONS: [&str; 3] = ["map", "tmx", "json"];
+
+/// The glyphs that can be used in the map lines.
+pub const GLYPHS: [char; 13] = [
+    '[', '-', ']', '_', '8', '4', '*', '=', '~', 'o', '^', '>', 'v',
+];
+
+/// Height of one row of terrain, the same as the height of a ground tile.
+pub const ROW_HEIGHT: f32 = 64.0;
+
+/// How far above the tiles they stand on things are spawned.
+pub const SPAWN_OFFSET: f32 = 91.0;
+
+/// Where a glyph places its tile relative to the draw position and how far it then moves the
+/// draw position to the right. `None` for unknown glyphs.
+pub fn glyph_layout(glyph: char) -> Option<(f32, f32)> {
+    match glyph {
+        '[' => Some((0.0, (TileType::Left.width() / 2.0) + 32.0)),
+        '-' | '8' | '=' | '~' | 'o' | '^' | '>' | 'v' => {
+            Some((0.0, (TileType::Center.width() / 2.0) + 32.0))
+        }
+        ']' => Some((-12.0, (TileType::Right.width() / 2.0) + 32.0)),
+        '_' => Some((0.0, 100.0)),
+        '4' | '*' => Some((0.0, TileType::Center.width())),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileType {
+    Left,
+    Center,
+    Right,
+    /// Only stops what lands on it from above.
+    OneWay,
+    /// Slippery, the player keeps sliding on it.
+    Ice,
+    /// Bounces back what lands on it.
+    Bouncy,
+    /// Hurts the player touching it.
+    Spikes,
+}
+
+impl TileType {
+    /// Width of the tile in the world. Matches the width of its image.
+    pub fn width(&self) -> f32 {
+        match self {
+            TileType::Left | TileType::Right => 40.0,
+            _ => 64.0,
+        }
+    }
+
+    /// The tile a glyph places, `None` for the glyphs without a tile and unknown ones.
+    pub fn of_glyph(glyph: char) -> Option<Self> {
+        match glyph {
+            '[' => Some(TileType::Left),
+            '-' | '8' | '4' | '*' => Some(TileType::Center),
+            ']' => Some(TileType::Right),
+            '=' => Some(TileType::OneWay),
+            '~' => Some(TileType::Ice),
+            'o' => Some(TileType::Bouncy),
+            '^' => Some(TileType::Spikes),
+            _ => None,
+        }
+    }
+}
+
+/// How a platform moves, set for the `>` glyphs by the `.path` directive:
+///
+/// ```text
+/// .path ping_pong 100 192,0
+/// .path loop 80 128,0 128,128 0,128
+/// ```
+///
+/// The mode is followed by the speed in pixels per second and the points of the path, relative
+/// to where the platform is placed, which is the first point. Like `.height` the Y of the points
+/// grows upwards. Without a `.path` the platforms go back and forth three tiles to the right.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlatformMotion {
+    /// Goes to the last point of the path, then back to the first one.
+    PingPong(PlatformPath),
+    /// Goes to the last point of the path, then straight on to the first one.
+    Loop(PlatformPath),
+    /// Stays in place until the player stands on it, then falls.
+    Falling,
+}
+
+/// The points a moving platform goes through, in world coordinates relative to where it is placed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlatformPath {
+    /// How fast the platform goes along the path, in pixels per second.
+    pub speed: f32,
+    /// Starts with the origin, where the platform is placed.
+    pub points: Vec<na::Vector2<f32>>,
+}
+
+impl Default for PlatformMotion {
+    fn default() -> Self {
+        PlatformMotion::PingPong(PlatformPath {
+            speed: 100.0,
+            points: vec![na::Vector2::zeros(), na::Vector2::new(192.0, 0.0)],
+        })
+    }
+}
+
+impl PlatformMotion {
+    /// Parse the arguments of a `.path` directive, like `ping_pong 100 192,0`.
+    pub fn parse(arguments: &str) -> Result<Self, String> {
+        let mut arguments = arguments.split_whitespace();
+
+        let mode = arguments.next().unwrap_or_default();
+
+        if mode != "ping_pong" && mode != "loop" {
+            return Err(format!(
+                "invalid path mode `{}`, expected ping_pong or loop",
+                mode
+            ));
+        }
+
+        let speed = arguments.next().unwrap_or_default();
+        let speed = match speed.parse::<f32>() {
+            Ok(speed) if speed.is_finite() && speed > 0.0 => speed,
+            _ => {
+                return Err(format!(
+                    "invalid path speed `{}`, expected a number above 0",
+                    speed
+                ))
+            }
+        };
+
+        let mut points = vec![na::Vector2::zeros()];
+
+        for point in arguments {
+            let coordinates = point
+                .split_once(',')
+                .and_then(|(x, y)| Some((x.parse::<f32>().ok()?, y.parse::<f32>().ok()?)))
+                .filter(|(x, y)| x.is_finite() && y.is_finite());
+
+            match coordinates {
+                Some((x, y)) => points.push(na::Vector2::new(x, -y)),
+                None => return Err(format!("invalid path point `{}`, expected X,Y", point)),
+            }
+        }
+
+        if points.len() < 2 {
+            return Err(String::from(
+                "the path has no points, expected X,Y after the speed",
+            ));
+        }
+
+        let path = PlatformPath { speed, points };
+
+        Ok(if mode == "loop" {
+            PlatformMotion::Loop(path)
+        } else {
+            PlatformMotion::PingPong(path)
+        })
+    }
+
+    /// Where the platform is, relative to where it was placed, after moving for the provided time
+    /// in seconds. A falling platform does not follow a path, it stays at the origin.
+    pub fn offset_at(&self, time: f32) -> na::Vector2<f32> {
+        let (path, looping) = match self {
+            PlatformMotion::PingPong(path) => (path, false),
+            PlatformMotion::Loop(path) => (path, true),
+            PlatformMotion::Falling => return na::Vector2::zeros(),
+        };
+
+        // A loop goes back to the origin as part of the path.
+        let mut points = path.points.clone();
+
+        if looping {
+            points.push(points[0]);
+        }
+
+        let length = points
+            .windows(2)
+            .map(|segment| (segment[1] - segment[0]).norm())
+            .sum::<f32>();
+
+        if length <= 0.0 {
+            return points[0];
+        }
+
+        let mut distance = (path.speed * time) % if looping { length } else { 2.0 * length };
+
+        // On the way back a ping pong goes the same distance from the other end.
+        if distance > length {
+            distance = 2.0 * length - distance;
+        }
+
+        for segment in points.windows(2) {
+            let along = segment[1] - segment[0];
+            let segment_length = along.norm();
+
+            if distance <= segment_length && segment_length > 0.0 {
+                return segment[0] + along * (distance / segment_length);
+            }
+
+            distance -= segment_length;
+        }
+
+        points[points.len() - 1]
+    }
+}
+
+/// How an enemy behaves. Every kind of enemy in the [`Roster`] has its own, which the `.enemy`
+/// directive changes for the enemies on the following lines:
+///
+/// ```text
+/// .enemy speed=120 sight=700 reload=0.8
+/// .enemy speed=0 flee=0
+/// ```
+///
+/// Each setting is a name and a number, the settings left out keep the ones of the kind. An enemy
+/// with a speed of 0 stands guard instead of patrolling and one with a flee of 0 never runs away.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnemyBehaviour {
+    /// How fast the enemy walks while it patrols, in pixels per second. It runs away faster.
+    pub speed: f32,
+    /// How far away the enemy sees the player from.
+    pub sight: f32,
+    /// How long the enemy stays alert before it attacks the player it saw, in seconds.
+    pub alert: f32,
+    /// How long the enemy waits between two shots, in seconds.
+    pub reload: f32,
+    /// The part of its health, from 0 to 1, below which the enemy runs away from the player.
+    pub flee: f32,
+}
+
+impl Default for EnemyBehaviour {
+    fn default() -> Self {
+        Self {
+            speed: 60.0,
+            sight: 500.0,
+            alert: 0.5,
+            reload: 1.5,
+            flee: 0.3,
+        }
+    }
+}
+
+impl EnemyBehaviour {
+    /// The names of the settings.
+    pub const NAMES: [&'static str; 5] = ["speed", "sight", "alert", "reload", "flee"];
+
+    /// Change the settings from the arguments of an `.enemy` directive, like `speed=120 sight=700`.
+    pub fn set(&mut self, arguments: &str) -> Result<(), String> {
+        for setting in arguments.split_whitespace() {
+            let (name, value) = setting
+                .split_once('=')
+                .ok_or_else(|| format!("invalid setting `{}`, expected NAME=NUMBER", setting))?;
+
+            let value = match value.parse::<f32>() {
+                Ok(value) if value.is_finite() && value >= 0.0 => value,
+                _ => {
+                    return Err(format!(
+                        "invalid {} `{}`, expected a number of at least 0",
+                        name, value
+                    ))
+                }
+            };
+
+            match name {
+                "speed" => self.speed = value,
+                "sight" => self.sight = value,
+                "alert" => self.alert = value,
+                "reload" => self.reload = value,
+                "flee" if value <= 1.0 => self.flee = value,
+                "flee" => {
+                    return Err(format!(
+                        "invalid flee `{}`, expected a number from 0 to 1",
+                        value
+                    ))
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown enemy setting `{}`, expected one of: {}",
+                        name,
+                        Self::NAMES.join(", ")
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Where something is placed in the world and the glyph in the .map file that placed it.
+/// The line and the column are 0 for things placed by a Tiled map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spawn {
+    pub position: na::Point2<f32>,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Everything a .map file (or a Tiled map) describes, laid out in world coordinates.
+///
+/// The tiles of height 0 are centered on Y 0 and the world grows to the right from X 0.
+#[derive(Debug, PartialEq)]
+pub struct Level {
+    pub tiles: Vec<(TileType, Spawn)>,
+    pub platforms: Vec<(PlatformMotion, Spawn)>,
+    /// The name of the kind of each enemy in the [`Roster`], and how it behaves.
+    pub enemies: Vec<(String, EnemyBehaviour, Spawn)>,
+    pub barrels: Vec<Spawn>,
+    pub player: Spawn,
+
+    /// The end quote displayed on the win screen.
+    pub end: Option<String>,
+    /// The weapon the player starts with, one of [`WEAPONS`].
+    pub weapon: String,
+    /// What the level changes about the physics and the player.
+    pub tuning: Tuning,
+}
+
+/// The physics and player settings a level overrides. `None` keeps the game's default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tuning {
+    pub gravity: Option<f32>,
+    pub player_ammo: Option<u32>,
+    pub player_health: Option<i32>,
+    /// File name of the music in `resources/audio`.
+    pub music: Option<String>,
+    /// File name of the background image in `resources/images`.
+    pub background: Option<String>,
+    /// Whether the player can jump a second time before landing.
+    pub double_jump: Option<bool>,
+}
+
+impl Tuning {
+    /// The names of the settings, which are also the names of their directives without the `.`.
+    pub const NAMES: [&'static str; 6] = [
+        "gravity",
+        "player_ammo",
+        "player_health",
+        "music",
+        "background",
+        "double_jump",
+    ];
+
+    /// Set the setting with the provided name from its text. `None` if there is no setting
+    /// with that name.
+    pub fn set(&mut self, name: &str, value: &str) -> Option<Result<(), String>> {
+        let value = value.trim();
+
+        let result = match name {
+            "gravity" => match value.parse::<f32>() {
+                Ok(gravity) if gravity.is_finite() => {
+                    self.gravity = Some(gravity);
+                    Ok(())
+                }
+                _ => Err(format!("invalid gravity `{}`, expected a number", value)),
+            },
+
+            "player_ammo" => value
+                .parse()
+                .map(|ammo| self.player_ammo = Some(ammo))
+                .map_err(|_| format!("invalid ammo `{}`, expected a whole number", value)),
+
+            "player_health" => match value.parse() {
+                Ok(health) if health > 0 => {
+                    self.player_health = Some(health);
+                    Ok(())
+                }
+                _ => Err(format!(
+                    "invalid health `{}`, expected a whole number above 0",
+                    value
+                )),
+            },
+
+            "music" | "background" if value.is_empty() => {
+                Err(format!("expected the file name of the {}", name))
+            }
+
+            "music" => {
+                self.music = Some(value.to_string());
+                Ok(())
+            }
+
+            "background" => {
+                self.background = Some(value.to_string());
+                Ok(())
+            }
+
+            // Tiled saves its boolean properties as true and false.
+            "double_jump" => match value {
+                "on" | "true" => {
+                    self.double_jump = Some(true);
+                    Ok(())
+                }
+                "off" | "false" => {
+                    self.double_jump = Some(false);
+                    Ok(())
+                }
+                _ => Err(format!(
+                    "invalid double jump `{}`, expected on or off",
+                    value
+                )),
+            },
+
+            _ => return None,
+        };
+
+        Some(result)
+    }
+}
+
+/// An error in a .map file, pointing at the line and the column (both starting from 1) that caused it.
+///
+/// The line is 0 when the error is about the whole map rather than a position in it.
+#[derive(Debug)]
+pub struct MapError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl MapError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        }
+    }
+}
+
+impl Error for MapError {}
+
+/// Something in a .map file that is allowed but most likely a mistake.
+#[derive(Debug)]
+pub struct MapWarning {
+    /// The line and the column the warning points at. `None` if it is about the whole map.
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl fmt::Display for MapWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Level {
+    /// Load a level from the source of a file with one of the [`LEVEL_EXTENSIONS`], with the
+    /// kinds of enemies of the provided roster.
+    pub fn from_source(extension: &str, source: &str, roster: &Roster) -> Result<Self, MapError> {
+        match extension {
+            "tmx" => Self::from_tmx(source, roster),
+            "json" => Self::from_tiled_json(source, roster),
+            _ => Self::parse(source, roster),
+        }
+    }
+}
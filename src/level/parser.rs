@@ -0,0 +1,261 @@
+use rapier2d::na;
+
+use super::{
+    glyph_layout, EnemyBehaviour, Level, MapError, PlatformMotion, Roster, Spawn, TileType, Tuning,
+    ROW_HEIGHT, SPAWN_OFFSET, WEAPONS,
+};
+
+impl Level {
+    /// Parse the source of a .map file, with the kinds of enemies of the provided roster.
+    pub fn parse(source: &str, roster: &Roster) -> Result<Self, MapError> {
+        // The Y position of the tiles and of the things spawned on top of them, for the current height.
+        let mut tile_y = 0.0;
+        let mut spawn_y = -SPAWN_OFFSET;
+
+        let mut draw_pos = 0.;
+
+        let mut tiles = vec![];
+        let mut platforms = vec![];
+        let mut enemies = vec![];
+        let mut barrels = vec![];
+
+        let mut player: Option<Spawn> = None;
+        // The position of the `[` of the platform that is currently open.
+        let mut platform: Option<(usize, usize)> = None;
+
+        let mut end = None;
+        let mut weapon = None;
+        let mut tuning = Tuning::default();
+        // How the moving platforms on the following lines move.
+        let mut path = PlatformMotion::default();
+        // The arguments of the last `.enemy` directive, which change how the enemies on the
+        // following lines behave.
+        let mut orders = String::new();
+
+        let mut line_no = 0;
+
+        for (index, line) in source.lines().enumerate() {
+            line_no = index + 1;
+
+            let exp = line.split(' ').collect::<Vec<_>>();
+
+            if exp[0].starts_with('.') {
+                // Column of the first argument of the directive.
+                let argument_column = exp[0].chars().count() + 2;
+
+                match exp[0].trim_end() {
+                    ".end" => {
+                        end = Some(exp[1..].join(" "));
+                    }
+
+                    ".using_weapon" => {
+                        let using_weapon = exp[1..].join(" ").trim().to_string();
+
+                        if !WEAPONS.contains(&using_weapon.as_str()) {
+                            return Err(MapError::new(
+                                line_no,
+                                argument_column,
+                                format!(
+                                    "unknown weapon `{}`, expected one of: {}",
+                                    using_weapon,
+                                    WEAPONS.join(", ")
+                                ),
+                            ));
+                        }
+
+                        weapon = Some(using_weapon);
+                    }
+
+                    ".height" => {
+                        let rows = exp[1..].join(" ");
+
+                        let rows = rows.trim().parse::<i32>().map_err(|_| {
+                            MapError::new(
+                                line_no,
+                                argument_column,
+                                format!(
+                                    "invalid height `{}`, expected a whole number of rows",
+                                    rows.trim()
+                                ),
+                            )
+                        })?;
+
+                        tile_y = -(rows as f32) * ROW_HEIGHT;
+                        spawn_y = tile_y - SPAWN_OFFSET;
+                    }
+
+                    ".path" => {
+                        path = PlatformMotion::parse(&exp[1..].join(" "))
+                            .map_err(|e| MapError::new(line_no, argument_column, e))?;
+                    }
+
+                    ".enemy" => {
+                        orders = exp[1..].join(" ");
+
+                        EnemyBehaviour::default()
+                            .set(&orders)
+                            .map_err(|e| MapError::new(line_no, argument_column, e))?;
+                    }
+
+                    ".comment" => {
+                        // Do nothing. ¯\_(ツ)_/¯
+                    }
+
+                    directive => match tuning.set(&directive[1..], &exp[1..].join(" ")) {
+                        Some(result) => {
+                            result.map_err(|e| MapError::new(line_no, argument_column, e))?
+                        }
+                        None => {
+                            return Err(MapError::new(
+                                line_no,
+                                1,
+                                format!("unknown directive `{}`", directive),
+                            ))
+                        }
+                    },
+                }
+            } else {
+                for (column, id) in line.chars().enumerate() {
+                    let column = column + 1;
+
+                    if id.is_whitespace() {
+                        continue;
+                    }
+
+                    // Every kind of enemy is laid out like the `8` of the gopher.
+                    let kind = roster.kind_of_glyph(id);
+                    let layout = match kind {
+                        Some(_) => glyph_layout('8'),
+                        None if id == '8' => None,
+                        None => glyph_layout(id),
+                    };
+
+                    let (offset, advance) = layout.ok_or_else(|| {
+                        MapError::new(line_no, column, format!("unknown glyph `{}`", id))
+                    })?;
+
+                    let tile = Spawn {
+                        position: na::Point2::new(draw_pos + offset, tile_y),
+                        line: line_no,
+                        column,
+                    };
+                    let spawn = Spawn {
+                        position: na::Point2::new(draw_pos + offset, spawn_y),
+                        ..tile
+                    };
+
+                    if let Some(kind) = kind {
+                        let mut behaviour = kind.behaviour.clone();
+                        behaviour
+                            .set(&orders)
+                            .expect("the `.enemy` directive is checked when it is read");
+
+                        tiles.push((TileType::Center, tile));
+                        enemies.push((kind.name.clone(), behaviour, spawn));
+
+                        draw_pos += advance;
+                        continue;
+                    }
+
+                    match id {
+                        '[' => {
+                            if let Some((open_line, open_column)) = platform {
+                                return Err(MapError::new(
+                                    line_no,
+                                    column,
+                                    format!(
+                                        "`[` opens a new platform before the one at {}:{} is closed with `]`",
+                                        open_line, open_column
+                                    ),
+                                ));
+                            }
+
+                            platform = Some((line_no, column));
+
+                            tiles.push((TileType::Left, tile));
+                        }
+
+                        '-' => {
+                            tiles.push((TileType::Center, tile));
+                        }
+
+                        ']' => {
+                            if platform.take().is_none() {
+                                return Err(MapError::new(
+                                    line_no,
+                                    column,
+                                    "`]` closes a platform that was never opened with `[`",
+                                ));
+                            }
+
+                            tiles.push((TileType::Right, tile));
+                        }
+
+                        '_' => {}
+
+                        '4' => {
+                            if let Some(first) = player {
+                                return Err(MapError::new(
+                                    line_no,
+                                    column,
+                                    format!(
+                                        "duplicate player, the first one is at {}:{}",
+                                        first.line, first.column
+                                    ),
+                                ));
+                            }
+
+                            tiles.push((TileType::Center, tile));
+                            player = Some(spawn);
+                        }
+
+                        '*' => {
+                            tiles.push((TileType::Center, tile));
+                            barrels.push(spawn);
+                        }
+
+                        '=' | '~' | 'o' | '^' => {
+                            tiles.push((TileType::of_glyph(id).unwrap(), tile));
+                        }
+
+                        '>' => {
+                            platforms.push((path.clone(), tile));
+                        }
+
+                        'v' => {
+                            platforms.push((PlatformMotion::Falling, tile));
+                        }
+
+                        _ => unreachable!(),
+                    }
+
+                    draw_pos += advance;
+                }
+            }
+        }
+
+        if let Some((open_line, open_column)) = platform {
+            return Err(MapError::new(
+                open_line,
+                open_column,
+                "platform is never closed with `]`",
+            ));
+        }
+
+        let player = player.ok_or_else(|| {
+            MapError::new(line_no.max(1), 1, "the map does not contain a player (`4`)")
+        })?;
+
+        Ok(Self {
+            tiles,
+            platforms,
+            enemies,
+            barrels,
+            player,
+            end,
+            // Default to the turbofish gun if the map does not say which weapon to start with.
+            weapon: weapon.unwrap_or_else(|| String::from(WEAPONS[0])),
+            tuning,
+        })
+    }
+}
@@ -0,0 +1,168 @@
+use serde::Deserialize;
+
+use super::{EnemyBehaviour, GLYPHS};
+
+/// The kinds of enemies, read from `resources/enemies.toml`. Each kind is an `[[enemy]]` table:
+///
+/// ```toml
+/// [[enemy]]
+/// name = "gopher"
+/// glyph = "8"
+/// sprite = "gopher.png"
+/// health = 100
+/// score = 100
+/// behaviour = { speed = 60 }
+/// weapon = { sprite = "Some(gun).png", damage = 10 }
+/// explosion = { particles = 50 }
+/// ```
+///
+/// The `behaviour` of a kind can set any of the settings of the `.enemy` directive, see
+/// [`EnemyBehaviour`]. A kind without a `weapon` runs into the player instead of shooting it.
+/// Unknown keys are errors rather than settings silently left at their default.
+///
+/// One of the kinds has to have the glyph `8`, which most maps and the generated levels use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Roster {
+    #[serde(rename = "enemy")]
+    pub enemies: Vec<EnemyKind>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnemyKind {
+    /// What the Tiled maps call the kind, in the `kind` property of their enemies.
+    pub name: String,
+    /// The glyph that places an enemy of this kind in a .map file, on a center tile like `8`.
+    pub glyph: char,
+    /// The image of the enemy in `resources/images`, looking to the left.
+    pub sprite: String,
+    /// The color the image is tinted with, white by default.
+    #[serde(default = "EnemyKind::white")]
+    pub tint: [u8; 3],
+    pub health: i32,
+    /// The points the player scores for destroying one.
+    pub score: u32,
+    /// How fast the enemy jumps up while it walks, in pixels per second. 0 if it does not jump.
+    #[serde(default)]
+    pub jump: f32,
+    #[serde(default)]
+    pub behaviour: EnemyBehaviour,
+    pub weapon: Option<EnemyWeapon>,
+    #[serde(default)]
+    pub explosion: Explosion,
+}
+
+/// The gun an enemy shoots the player with.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnemyWeapon {
+    /// The image of the gun in `resources/images`, pointing to the left.
+    pub sprite: String,
+    /// The health the player loses when a bullet hits it.
+    pub damage: i32,
+    /// How fast the bullets go, in pixels per second.
+    #[serde(default = "EnemyWeapon::bullet_speed")]
+    pub bullet_speed: f32,
+}
+
+/// The particles an enemy blows up into.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Explosion {
+    pub particles: usize,
+    /// Each particle gets a color between these two.
+    pub colors: [[u8; 3]; 2],
+}
+
+impl Default for Explosion {
+    fn default() -> Self {
+        Self {
+            particles: 50,
+            colors: [[255, 0, 0], [255, 255, 0]],
+        }
+    }
+}
+
+impl EnemyKind {
+    fn white() -> [u8; 3] {
+        [255, 255, 255]
+    }
+}
+
+impl EnemyWeapon {
+    fn bullet_speed() -> f32 {
+        450.0
+    }
+}
+
+impl Default for Roster {
+    /// The roster the game ships with, for the tools that check maps outside of its resources.
+    fn default() -> Self {
+        Self::parse(include_str!("../../resources/enemies.toml"))
+            .expect("the roster of the game is valid")
+    }
+}
+
+impl Roster {
+    /// Parse the source of an enemies.toml file.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let roster: Self = toml::from_str(source).map_err(|e| e.to_string())?;
+
+        if roster.enemies.is_empty() {
+            return Err(String::from("there are no kinds of enemies"));
+        }
+
+        for (id, kind) in roster.enemies.iter().enumerate() {
+            let taken = kind.glyph != '8' && GLYPHS.contains(&kind.glyph);
+
+            if taken || kind.glyph == '.' || kind.glyph.is_whitespace() {
+                return Err(format!(
+                    "the glyph `{}` of the enemy `{}` is already used by the maps",
+                    kind.glyph, kind.name
+                ));
+            }
+
+            if kind.health <= 0 {
+                return Err(format!(
+                    "the enemy `{}` has a health of {}, expected a whole number above 0",
+                    kind.name, kind.health
+                ));
+            }
+
+            if let Some(weapon) = kind.weapon.as_ref().filter(|weapon| weapon.damage < 0) {
+                return Err(format!(
+                    "the weapon of the enemy `{}` has a damage of {}, expected a whole number of 0 or more",
+                    kind.name, weapon.damage
+                ));
+            }
+
+            if let Some(other) = roster.enemies[..id]
+                .iter()
+                .find(|other| other.name == kind.name || other.glyph == kind.glyph)
+            {
+                return Err(format!(
+                    "the enemies `{}` and `{}` have the same name or glyph",
+                    other.name, kind.name
+                ));
+            }
+        }
+
+        if roster.kind_of_glyph('8').is_none() {
+            return Err(String::from(
+                "there is no enemy with the glyph `8`, which the maps place the most",
+            ));
+        }
+
+        Ok(roster)
+    }
+
+    /// The kind with the provided name.
+    pub fn kind(&self, name: &str) -> Option<&EnemyKind> {
+        self.enemies.iter().find(|kind| kind.name == name)
+    }
+
+    /// The kind the provided glyph places.
+    pub fn kind_of_glyph(&self, glyph: char) -> Option<&EnemyKind> {
+        self.enemies.iter().find(|kind| kind.glyph == glyph)
+    }
+}
@@ -0,0 +1,127 @@
+use super::glyph_layout;
+
+/// A .map file broken into its directives and its glyphs, one glyph per column along with the
+/// height (in rows) it sits on.
+///
+/// Unlike a [`Level`](super::Level) it does not have to be valid, which is what the level editor
+/// works with while the map is half done.
+#[derive(Debug, Clone, Default)]
+pub struct MapSource {
+    /// Every directive except valid `.height` ones, in the order they appear in the file, along with the
+    /// column it comes before. Directives like `.path` only apply to the glyphs after them, so
+    /// they are written back where they were.
+    pub directives: Vec<(usize, String)>,
+    /// The glyphs and their height, from left to right.
+    pub columns: Vec<(char, i32)>,
+}
+
+impl MapSource {
+    /// Split the text of a .map file. Unknown glyphs and invalid heights are kept as they are
+    /// so nothing is lost when saving the map again.
+    pub fn from_text(source: &str) -> Self {
+        let mut directives = vec![];
+        let mut columns = vec![];
+
+        let mut height = 0;
+
+        for line in source.lines() {
+            let rows = line.strip_prefix(".height").map(|rows| rows.trim().parse());
+
+            if let Some(Ok(rows)) = rows {
+                height = rows;
+            } else if line.starts_with('.') {
+                directives.push((columns.len(), line.to_string()));
+            } else {
+                columns.extend(
+                    line.chars()
+                        .filter(|glyph| !glyph.is_whitespace())
+                        .map(|glyph| (glyph, height)),
+                );
+            }
+        }
+
+        Self {
+            directives,
+            columns,
+        }
+    }
+
+    /// Write the map back as the text of a .map file. A new segment is started before the
+    /// directives of a column, and with a `.height` directive every time the height changes.
+    pub fn to_text(&self) -> String {
+        let mut directives = self.directives.iter().collect::<Vec<_>>();
+        directives.sort_by_key(|(column, _)| *column);
+
+        let mut directives = directives.into_iter().peekable();
+
+        let mut text = String::new();
+        let mut height = 0;
+        let mut segment = String::new();
+
+        for (id, &(glyph, glyph_height)) in self.columns.iter().enumerate() {
+            let anchored = directives.peek().is_some_and(|(column, _)| *column <= id);
+
+            if anchored || glyph_height != height {
+                if !segment.is_empty() {
+                    text.push_str(&segment);
+                    text.push('\n');
+                    segment.clear();
+                }
+
+                while let Some((_, directive)) = directives.next_if(|(column, _)| *column <= id) {
+                    text.push_str(directive);
+                    text.push('\n');
+                }
+
+                if glyph_height != height {
+                    text.push_str(&format!(".height {}\n", glyph_height));
+                    height = glyph_height;
+                }
+            }
+
+            segment.push(glyph);
+        }
+
+        if !segment.is_empty() {
+            text.push_str(&segment);
+            text.push('\n');
+        }
+
+        // The directives after the last glyph.
+        for (_, directive) in directives {
+            text.push_str(directive);
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// Remove the provided column. The directives after it move along with the glyphs.
+    pub fn remove(&mut self, column: usize) {
+        self.columns.remove(column);
+
+        for (anchor, _) in &mut self.directives {
+            if *anchor > column {
+                *anchor -= 1;
+            }
+        }
+    }
+
+    /// The X position of every column followed by the X position right after the last one,
+    /// laid out the same way as [`Level::parse`](super::Level::parse) does it.
+    pub fn layout(&self) -> Vec<f32> {
+        let mut draw_pos = 0.0;
+        let mut positions = Vec::with_capacity(self.columns.len() + 1);
+
+        for &(glyph, _) in &self.columns {
+            positions.push(draw_pos);
+
+            // Unknown glyphs still take up some space so that they can be seen and removed.
+            draw_pos += glyph_layout(glyph).map_or(64.0, |(_, advance)| advance);
+        }
+
+        positions.push(draw_pos);
+
+        positions
+    }
+}
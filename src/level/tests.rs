@@ -0,0 +1,429 @@
+//! Parses maps from text, without the game or a window.
+
+use super::{
+    jump_reach, Level, MapError, MapSource, PlatformMotion, Roster, TileType, GRAVITY, ROW_HEIGHT,
+    SPAWN_OFFSET,
+};
+
+/// Parse a .map file with the roster the game ships with.
+fn parse(source: &str) -> Level {
+    Level::parse(source, &Roster::default()).unwrap()
+}
+
+/// The error of a .map file that does not parse.
+fn parse_error(source: &str) -> MapError {
+    Level::parse(source, &Roster::default()).unwrap_err()
+}
+
+/// The messages of the warnings about a map with an end quote and an enemy, so that only the
+/// jumps are warned about.
+fn jump_warnings(map: &str) -> Vec<String> {
+    let level = parse(&format!(".end Done\n{}", map));
+
+    level
+        .lint()
+        .into_iter()
+        .map(|warning| warning.message)
+        .collect()
+}
+
+#[test]
+fn parser_lays_out_segments_on_their_height() {
+    let level = parse("[-4-]\n.height 2\n[-8-]");
+
+    assert_eq!(level.tiles.len(), 10);
+    assert_eq!(level.tiles[0].0, TileType::Left);
+    assert_eq!(level.tiles[0].1.position.y, 0.0);
+    assert_eq!(level.tiles[4].0, TileType::Right);
+    assert_eq!(level.tiles[5].1.position.y, -2.0 * ROW_HEIGHT);
+
+    assert_eq!((level.player.line, level.player.column), (1, 3));
+    assert_eq!(level.player.position.y, -SPAWN_OFFSET);
+
+    let (kind, _, enemy) = &level.enemies[0];
+    assert_eq!(kind, "gopher");
+    assert_eq!((enemy.line, enemy.column), (3, 3));
+    assert_eq!(enemy.position.y, -2.0 * ROW_HEIGHT - SPAWN_OFFSET);
+
+    // Things are laid out from left to right, one segment after the other.
+    assert!(level
+        .tiles
+        .windows(2)
+        .all(|pair| pair[0].1.position.x < pair[1].1.position.x));
+}
+
+#[test]
+fn parser_points_at_what_is_wrong() {
+    let error = parse_error("[-4-]\n[-Z-]");
+    assert_eq!((error.line, error.column), (2, 3));
+    assert!(error.message.contains("unknown glyph `Z`"));
+
+    let error = parse_error("[-4-]\n.height two");
+    assert_eq!((error.line, error.column), (2, 9));
+
+    let error = parse_error(".speed 10\n[-4-]");
+    assert_eq!((error.line, error.column), (1, 1));
+
+    let error = parse_error("[-4-\n[-8-]");
+    assert_eq!((error.line, error.column), (2, 1));
+
+    let error = parse_error("[-8-]");
+    assert!(error.message.contains("does not contain a player"));
+}
+
+#[test]
+fn lint_warns_about_gaps_too_wide_to_jump() {
+    let reach = jump_reach(0.0, GRAVITY).unwrap();
+    let (_, space) = super::glyph_layout('_').unwrap();
+    let spaces = (reach / space) as usize + 1;
+
+    assert!(jump_warnings("[-4-]__[-8-]").is_empty());
+
+    let warnings = jump_warnings(&format!("[-4-]{}[-8-]", "_".repeat(spaces)));
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("the player can only jump over"));
+
+    // Unless a platform carries the player over it.
+    let bridged = format!(
+        "[-4-]{}>{}[-8-]",
+        "_".repeat(spaces / 2),
+        "_".repeat(spaces / 2)
+    );
+    assert!(jump_warnings(&bridged).is_empty());
+}
+
+#[test]
+fn lint_warns_about_climbs_too_high_to_jump() {
+    assert!(jump_reach(ROW_HEIGHT, GRAVITY).is_some());
+    assert!(jump_reach(2.0 * ROW_HEIGHT, GRAVITY).is_none());
+
+    assert!(jump_warnings("[-4-]\n.height 1\n[-8-]").is_empty());
+
+    let warnings = jump_warnings("[-4-]\n.height 2\n[-8-]");
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].contains("cannot jump that high"));
+
+    // Down is always fine, and a lower gravity lets the player jump higher.
+    assert!(jump_warnings("[-4-]\n.height -10\n[-8-]").is_empty());
+    assert!(jump_warnings(".gravity 100\n[-4-]\n.height 2\n[-8-]").is_empty());
+}
+
+#[test]
+fn lint_stops_following_jumps_that_barely_come_down() {
+    assert_eq!(jump_reach(0.0, 0.0001), Some(f32::INFINITY));
+    assert!(jump_warnings(".gravity 0.0001\n[-4-]______[-8-]").is_empty());
+
+    // Even with next to no gravity the jump only gets so high.
+    assert_eq!(jump_reach(100.0 * ROW_HEIGHT, 0.0001), None);
+}
+
+#[test]
+fn map_source_keeps_directives_in_place() {
+    let source = "\
+.comment Two platforms that move their own way
+[-4--]
+.path loop 80 128,0 128,128
+_>_
+.path ping_pong 100 0,192
+.height 1
+_>_[--]
+";
+    let map = MapSource::from_text(source);
+
+    assert_eq!(map.to_text(), source);
+    assert_eq!(parse(&map.to_text()), parse(source));
+
+    let paths = parse(source)
+        .platforms
+        .into_iter()
+        .map(|(motion, _)| motion)
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        paths,
+        vec![
+            PlatformMotion::parse("loop 80 128,0 128,128").unwrap(),
+            PlatformMotion::parse("ping_pong 100 0,192").unwrap(),
+        ]
+    );
+}
+
+#[test]
+fn map_source_keeps_invalid_heights_in_place() {
+    let source = "\
+[-4-]
+.height up
+_
+.height 1
+[-8-]
+";
+    let map = MapSource::from_text(source);
+
+    assert_eq!(map.to_text(), source);
+    assert_eq!(map.directives, vec![(5, ".height up".to_string())]);
+}
+
+#[test]
+fn map_source_keeps_enemy_orders_in_place() {
+    let source = "\
+[-4--]_
+.enemy speed=0
+[-8-]_
+.enemy sight=900 flee=0
+[-8-]
+";
+    let map = MapSource::from_text(source);
+
+    assert_eq!(map.to_text(), source);
+    assert_eq!(parse(&map.to_text()), parse(source));
+
+    let level = parse(source);
+    let (guard, runner) = (&level.enemies[0].1, &level.enemies[1].1);
+
+    assert_eq!(guard.speed, 0.0);
+    assert_ne!(guard.sight, 900.0);
+    assert_ne!(runner.speed, 0.0);
+    assert_eq!(runner.sight, 900.0);
+}
+
+#[test]
+fn roster_needs_an_enemy_for_the_8_glyph() {
+    let source = r#"
+[[enemy]]
+name = "snake"
+glyph = "P"
+sprite = "snake.png"
+health = 50
+score = 150
+"#;
+
+    assert!(Roster::parse(source).is_err());
+    assert!(Roster::parse(&source.replace("\"P\"", "\"8\"")).is_ok());
+}
+
+#[test]
+fn roster_rejects_unknown_keys_and_negative_damage() {
+    let source = r#"
+[[enemy]]
+name = "gopher"
+glyph = "8"
+sprite = "gopher.png"
+health = 100
+score = 100
+behaviour = { speed = 0, sight = 900 }
+weapon = { sprite = "Some(gun).png", damage = 10 }
+"#;
+
+    let roster = Roster::parse(source).unwrap();
+    assert_eq!(roster.enemies[0].behaviour.speed, 0.0);
+    assert_eq!(roster.enemies[0].behaviour.sight, 900.0);
+
+    // A misspelled setting, or one outside of the behaviour, would otherwise be left at its default.
+    assert!(Roster::parse(&source.replace("sight", "sigth")).is_err());
+    assert!(Roster::parse(&source.replace("behaviour = {", "sight = 900\nbehaviour = {")).is_err());
+
+    assert!(Roster::parse(&source.replace("damage = 10", "damage = -10")).is_err());
+}
+
+/// A .tmx map with a tileset of classed tiles, a flipped tile, and objects with properties.
+const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="4" height="2" tilewidth="64" tileheight="64">
+ <properties>
+  <property name="end" value="Imported"/>
+  <property name="gravity" type="float" value="200"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="64" tileheight="64" tilecount="3" columns="3">
+  <tile id="0" class="left"/>
+  <tile id="1" type="spikes"/>
+ </tileset>
+ <layer id="1" name="ground" width="4" height="2">
+  <data encoding="csv">
+0,0,0,0,
+1,3,2147483650,0
+</data>
+ </layer>
+ <objectgroup id="2" name="things">
+  <object id="1" type="player" x="64" y="0" width="64" height="64"/>
+  <object id="2" class="enemy" name="Guard" x="128" y="0" width="64" height="64">
+   <properties>
+    <property name="kind" value="snake"/>
+    <property name="behaviour" value="speed=0"/>
+    <property name="double_jump" type="bool" value="true"/>
+   </properties>
+  </object>
+  <object id="3" class="barrel" gid="3" x="0" y="64" width="64" height="64"/>
+ </objectgroup>
+</map>
+"#;
+
+/// The same map as [`TMX`], exported as JSON.
+const TILED_JSON: &str = r#"{
+ "tilewidth": 64,
+ "tileheight": 64,
+ "properties": [
+  { "name": "end", "type": "string", "value": "Imported" },
+  { "name": "gravity", "type": "float", "value": 200 }
+ ],
+ "tilesets": [
+  {
+   "firstgid": 1,
+   "tiles": [{ "id": 0, "class": "left" }, { "id": 1, "type": "spikes" }]
+  }
+ ],
+ "layers": [
+  { "type": "tilelayer", "name": "ground", "width": 4, "data": [0, 0, 0, 0, 1, 3, 2147483650, 0] },
+  {
+   "type": "group",
+   "layers": [
+    {
+     "type": "objectgroup",
+     "objects": [
+      { "id": 1, "type": "player", "x": 64, "y": 0, "width": 64, "height": 64 },
+      {
+       "id": 2, "class": "enemy", "name": "Guard", "x": 128, "y": 0, "width": 64, "height": 64,
+       "properties": [
+        { "name": "kind", "type": "string", "value": "snake" },
+        { "name": "behaviour", "type": "string", "value": "speed=0" },
+        { "name": "double_jump", "type": "bool", "value": true }
+       ]
+      },
+      { "id": 3, "class": "barrel", "gid": 3, "x": 0, "y": 64, "width": 64, "height": 64 }
+     ]
+    }
+   ]
+  }
+ ]
+}"#;
+
+/// The line of the provided text in a source, starting from 1.
+fn line_of(source: &str, text: &str) -> usize {
+    source.lines().position(|line| line.contains(text)).unwrap() + 1
+}
+
+#[test]
+fn tiled_maps_import_tiles_objects_and_properties() {
+    let roster = Roster::default();
+
+    let tmx = Level::from_tmx(TMX, &roster).unwrap();
+    let json = Level::from_tiled_json(TILED_JSON, &roster).unwrap();
+
+    assert_eq!(tmx, json);
+
+    // The classes pick the type of the tiles, whichever way the spikes are flipped.
+    let types = tmx.tiles.iter().map(|(tile, _)| *tile).collect::<Vec<_>>();
+    assert_eq!(
+        types,
+        vec![TileType::Left, TileType::Center, TileType::Spikes]
+    );
+    assert!(tmx.tiles.iter().all(|(_, tile)| tile.position.y == 96.0));
+    assert_eq!(tmx.tiles[1].1.position.x, 96.0);
+
+    // Objects spawn at their centre, tile objects hang up from their position.
+    assert_eq!((tmx.player.position.x, tmx.player.position.y), (96.0, 32.0));
+    assert_eq!(
+        (tmx.barrels[0].position.x, tmx.barrels[0].position.y),
+        (32.0, 32.0)
+    );
+
+    let (kind, behaviour, _) = &tmx.enemies[0];
+    assert_eq!(kind, "snake");
+    assert_eq!(behaviour.speed, 0.0);
+
+    assert_eq!(tmx.end.as_deref(), Some("Imported"));
+    assert_eq!(tmx.tuning.gravity, Some(200.0));
+    assert_eq!(tmx.tuning.double_jump, Some(true));
+}
+
+#[test]
+fn tiled_maps_only_place_classed_objects_of_the_object_layers() {
+    let roster = Roster::default();
+
+    // The collision shapes of the tiles are not objects of the map, whatever their class.
+    let collision = TMX.replace(
+        r#"<tile id="1" type="spikes"/>"#,
+        r#"<tile id="1" type="spikes">
+   <objectgroup id="1"><object id="1" class="player" x="0" y="32" width="64" height="32"/></objectgroup>
+  </tile>"#,
+    );
+    assert_eq!(
+        Level::from_tmx(&collision, &roster).unwrap(),
+        Level::from_tmx(TMX, &roster).unwrap()
+    );
+
+    // Names are for the designer, only the class places something.
+    let named = TMX.replace(r#"type="player""#, r#"name="player""#);
+    assert!(Level::from_tmx(&named, &roster).is_err());
+
+    let named = TILED_JSON.replace(r#""type": "player""#, r#""name": "player""#);
+    assert!(Level::from_tiled_json(&named, &roster).is_err());
+}
+
+#[test]
+fn tiled_maps_need_tiles() {
+    let roster = Roster::default();
+
+    let empty = TMX.replace("1,3,2147483650,0", "0,0,0,0");
+    let error = Level::from_tmx(&empty, &roster).unwrap_err();
+    assert_eq!(error.line, line_of(&empty, "<map"));
+    assert!(error.message.contains("no tiles"));
+
+    let empty = TILED_JSON.replace("1, 3, 2147483650, 0", "0, 0, 0, 0");
+    assert!(Level::from_tiled_json(&empty, &roster).is_err());
+}
+
+#[test]
+fn tmx_errors_point_at_their_element() {
+    let roster = Roster::default();
+
+    let lava = TMX.replace(r#"class="left""#, r#"class="lava""#);
+    let error = Level::from_tmx(&lava, &roster).unwrap_err();
+    assert_eq!(error.line, line_of(&lava, "lava"));
+    assert!(error.message.contains("unknown class `lava`"));
+
+    let kind = TMX.replace(r#"value="snake""#, r#"value="cobol""#);
+    let error = Level::from_tmx(&kind, &roster).unwrap_err();
+    assert_eq!(error.line, line_of(&kind, r#"<object id="2""#));
+    assert!(error.message.contains("unknown kind `cobol`"));
+
+    let base64 = TMX.replace(r#"encoding="csv""#, r#"encoding="base64""#);
+    let error = Level::from_tmx(&base64, &roster).unwrap_err();
+    assert_eq!(error.line, line_of(&base64, "<data"));
+
+    let duplicate = TMX.replace(r#"class="barrel""#, r#"class="player""#);
+    let error = Level::from_tmx(&duplicate, &roster).unwrap_err();
+    assert_eq!(error.line, line_of(&duplicate, r#"<object id="3""#));
+
+    let broken = TMX.replace("</objectgroup>", "");
+    assert!(Level::from_tmx(&broken, &roster).unwrap_err().line > 0);
+}
+
+#[test]
+fn tiled_json_errors_name_what_is_wrong() {
+    let roster = Roster::default();
+
+    let error = |json: String| Level::from_tiled_json(&json, &roster).unwrap_err();
+
+    let lava = error(TILED_JSON.replace(r#""class": "left""#, r#""class": "lava""#));
+    assert_eq!(lava.line, 0);
+    assert!(lava.message.contains("tile 1 has the unknown class `lava`"));
+
+    let kind = error(TILED_JSON.replace(r#""value": "snake""#, r#""value": "cobol""#));
+    assert!(kind
+        .message
+        .contains("object 2 has the unknown kind `cobol`"));
+
+    let weapon = error(TILED_JSON.replace(
+        r#""name": "end", "type": "string""#,
+        r#""name": "using_weapon", "type": "string""#,
+    ));
+    assert!(weapon.message.contains("unknown weapon `Imported`"));
+
+    let encoding = error(TILED_JSON.replace(
+        "[0, 0, 0, 0, 1, 3, 2147483650, 0]",
+        r#""AAAAAA==", "encoding": "base64""#,
+    ));
+    assert!(encoding.message.contains("tile layer `ground`"));
+
+    // Broken JSON still has a position.
+    assert!(error(TILED_JSON.replace("\"layers\": [", "\"layers\": ")).line > 0);
+}
@@ -0,0 +1,663 @@
+use std::collections::HashMap;
+
+use rapier2d::na;
+use serde::Deserialize;
+use xml::{
+    attribute::OwnedAttribute,
+    common::Position,
+    reader::{EventReader, XmlEvent},
+};
+
+use super::{Level, MapError, PlatformMotion, Roster, Spawn, TileType, Tuning, WEAPONS};
+
+/// Tiled keeps whether a tile is flipped or rotated in the top bits of its global id.
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+/// A map made with Tiled, read from either of the formats it saves in.
+///
+/// The line and the column of the XML elements are kept to point errors at them in a .tmx file.
+/// A .json file has no such positions, its errors have a line of 0 and name what is wrong instead.
+#[derive(Default)]
+struct TiledMap {
+    /// Where the `<map>` element is.
+    position: (usize, usize),
+    tile_width: f32,
+    tile_height: f32,
+    properties: HashMap<String, String>,
+    /// The type that the class (or type) of the tiles that have one picks, by global tile id.
+    tile_classes: HashMap<u32, TileType>,
+    /// The width in tiles and the global tile ids, row by row, of every tile layer.
+    tile_layers: Vec<(usize, Vec<u32>)>,
+    objects: Vec<TiledObject>,
+}
+
+#[derive(Default)]
+struct TiledObject {
+    /// Where the `<object>` element is.
+    position: (usize, usize),
+    id: u32,
+    /// The class (or type) of the object, its name if it has none.
+    class: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// Tile objects are positioned from their bottom left corner instead of the top left one.
+    tile: bool,
+    properties: HashMap<String, String>,
+}
+
+impl Level {
+    /// Import a map saved by Tiled as .tmx. Tile layers must use the CSV encoding and tilesets
+    /// must be embedded in the map.
+    pub fn from_tmx(source: &str, roster: &Roster) -> Result<Self, MapError> {
+        TiledMap::from_tmx(source)?.into_level(roster)
+    }
+
+    /// Import a map exported by Tiled as JSON. Tile layers must use the CSV encoding and tilesets
+    /// must be embedded in the map.
+    pub fn from_tiled_json(source: &str, roster: &Roster) -> Result<Self, MapError> {
+        TiledMap::from_json(source)?.into_level(roster)
+    }
+}
+
+impl TiledMap {
+    fn from_tmx(source: &str) -> Result<Self, MapError> {
+        /// What the `<property>` elements being read belong to.
+        enum Owner {
+            Map,
+            Object,
+            Other,
+        }
+
+        let mut parser = EventReader::from_str(source);
+        let mut map = Self::default();
+
+        let mut owner = Owner::Map;
+        let mut first_gid = 0;
+        // The objects of a tileset are the collision shapes of its tiles, not things to place.
+        let mut in_tileset = false;
+        // The width of the tile layer whose `<data>` is being read, if any.
+        let mut data_width = None;
+        let mut layer_width = 0;
+
+        loop {
+            let event = parser.next().map_err(|e| {
+                let position = e.position();
+                MapError::new(
+                    position.row as usize + 1,
+                    position.column as usize + 1,
+                    e.msg(),
+                )
+            })?;
+
+            let position = parser.position();
+            let position = (position.row as usize + 1, position.column as usize + 1);
+            let error = |message: String| MapError::new(position.0, position.1, message);
+
+            match event {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let attribute = |key: &str| attribute(&attributes, key);
+                    let number = |key: &str| number(&attributes, key).map_err(error);
+
+                    match name.local_name.as_str() {
+                        "map" => {
+                            map.position = position;
+                            map.tile_width = number("tilewidth")?;
+                            map.tile_height = number("tileheight")?;
+                            owner = Owner::Map;
+                        }
+
+                        "tileset" => {
+                            if let Some(source) = attribute("source") {
+                                return Err(error(format!(
+                                    "external tileset `{}`, embed the tileset in the map instead",
+                                    source
+                                )));
+                            }
+
+                            first_gid = number("firstgid")? as u32;
+                            owner = Owner::Other;
+                            in_tileset = true;
+                        }
+
+                        "tile" if data_width.is_none() => {
+                            let class = attribute("class").or_else(|| attribute("type"));
+
+                            if let Some(class) = class.filter(|class| !class.is_empty()) {
+                                let id = first_gid + number("id")? as u32;
+                                let tile_type = tile_type(id, class).map_err(error)?;
+
+                                map.tile_classes.insert(id, tile_type);
+                            }
+                        }
+
+                        "layer" => {
+                            layer_width = number("width")? as usize;
+                            owner = Owner::Other;
+                        }
+
+                        "objectgroup" | "imagelayer" | "group" => owner = Owner::Other,
+
+                        "data" => {
+                            let encoding = attribute("encoding").unwrap_or("xml");
+
+                            if encoding != "csv" {
+                                return Err(error(format!(
+                                    "tile layer data encoded as `{}`, save it as CSV instead",
+                                    encoding
+                                )));
+                            }
+
+                            data_width = Some(layer_width);
+                        }
+
+                        "object" if !in_tileset => {
+                            let class = attribute("class")
+                                .or_else(|| attribute("type"))
+                                .unwrap_or_default();
+
+                            map.objects.push(TiledObject {
+                                position,
+                                id: number("id")? as u32,
+                                class: class.to_string(),
+                                x: number("x")?,
+                                y: number("y")?,
+                                width: number("width")?,
+                                height: number("height")?,
+                                tile: attribute("gid").is_some(),
+                                properties: HashMap::new(),
+                            });
+
+                            owner = Owner::Object;
+                        }
+
+                        "property" => {
+                            let property = attribute("name").map(String::from);
+                            let value = attribute("value").unwrap_or_default().to_string();
+
+                            let properties = match owner {
+                                Owner::Map => Some(&mut map.properties),
+                                Owner::Object => map.objects.last_mut().map(|o| &mut o.properties),
+                                Owner::Other => None,
+                            };
+
+                            if let (Some(properties), Some(property)) = (properties, property) {
+                                properties.insert(property, value);
+                            }
+                        }
+
+                        _ => {}
+                    }
+                }
+
+                XmlEvent::Characters(text) => {
+                    if let Some(width) = data_width {
+                        let data = text
+                            .split(',')
+                            .enumerate()
+                            .map(|(index, gid)| {
+                                gid.trim().parse::<u32>().map_err(|_| {
+                                    error(format!(
+                                        "invalid tile {} of the tile layer data `{}`, expected a tile id",
+                                        index,
+                                        gid.trim()
+                                    ))
+                                })
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+
+                        map.tile_layers.push((width, data));
+                    }
+                }
+
+                XmlEvent::EndElement { name } => match name.local_name.as_str() {
+                    "data" => data_width = None,
+                    "tileset" => in_tileset = false,
+                    "object" => owner = Owner::Other,
+                    _ => {}
+                },
+
+                XmlEvent::EndDocument => break,
+
+                _ => {}
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn from_json(source: &str) -> Result<Self, MapError> {
+        let json: JsonMap = serde_json::from_str(source)
+            .map_err(|e| MapError::new(e.line(), e.column(), e.to_string()))?;
+
+        let mut map = Self {
+            tile_width: json.tilewidth,
+            tile_height: json.tileheight,
+            properties: properties(json.properties),
+            ..Self::default()
+        };
+
+        for tileset in json.tilesets {
+            if let Some(source) = tileset.source {
+                return Err(MapError::new(
+                    0,
+                    0,
+                    format!(
+                        "external tileset `{}`, embed the tileset in the map instead",
+                        source
+                    ),
+                ));
+            }
+
+            for tile in tileset.tiles {
+                let class = if tile.class.is_empty() {
+                    tile.kind
+                } else {
+                    tile.class
+                };
+
+                if !class.is_empty() {
+                    let id = tileset.firstgid + tile.id;
+                    let tile_type = tile_type(id, &class).map_err(|e| MapError::new(0, 0, e))?;
+
+                    map.tile_classes.insert(id, tile_type);
+                }
+            }
+        }
+
+        map.add_json_layers(json.layers)?;
+
+        Ok(map)
+    }
+
+    /// Add the layers of a JSON map, and the layers inside its groups.
+    fn add_json_layers(&mut self, layers: Vec<JsonLayer>) -> Result<(), MapError> {
+        for layer in layers {
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    let name = layer.name;
+                    let data = serde_json::from_value::<Vec<u32>>(layer.data).map_err(|_| {
+                        MapError::new(
+                            0,
+                            0,
+                            format!(
+                                "tile layer `{}` is not saved as CSV, change its encoding to CSV",
+                                name
+                            ),
+                        )
+                    })?;
+
+                    self.tile_layers.push((layer.width, data));
+                }
+
+                "objectgroup" => {
+                    for object in layer.objects {
+                        let class = if object.class.is_empty() {
+                            object.kind
+                        } else {
+                            object.class
+                        };
+
+                        self.objects.push(TiledObject {
+                            position: (0, 0),
+                            id: object.id,
+                            class,
+                            x: object.x,
+                            y: object.y,
+                            width: object.width,
+                            height: object.height,
+                            tile: object.gid.is_some(),
+                            properties: properties(object.properties),
+                        });
+                    }
+                }
+
+                "group" => self.add_json_layers(layer.layers)?,
+
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lay the map out in world coordinates. Tiled positions are used as they are, so the tiles
+    /// of the map should be as big as the ground tiles (64x64).
+    fn into_level(self, roster: &Roster) -> Result<Level, MapError> {
+        let mut tiles = vec![];
+
+        for (width, data) in &self.tile_layers {
+            let width = (*width).max(1);
+
+            for (index, gid) in data.iter().enumerate() {
+                let gid = gid & !FLIP_FLAGS;
+
+                if gid == 0 {
+                    continue;
+                }
+
+                let tile_type = self
+                    .tile_classes
+                    .get(&gid)
+                    .copied()
+                    .unwrap_or(TileType::Center);
+
+                let left = (index % width) as f32 * self.tile_width;
+                let top = (index / width) as f32 * self.tile_height;
+
+                // The edge tiles are narrower than a cell, keep them against the tiles next to them.
+                let x = match tile_type {
+                    TileType::Left => left + self.tile_width - tile_type.width() / 2.0,
+                    TileType::Right => left + tile_type.width() / 2.0,
+                    _ => left + self.tile_width / 2.0,
+                };
+
+                tiles.push((
+                    tile_type,
+                    Spawn {
+                        position: na::Point2::new(x, top + self.tile_height / 2.0),
+                        line: 0,
+                        column: 0,
+                    },
+                ));
+            }
+        }
+
+        // Keep the tiles from left to right like they are in a .map file.
+        tiles.sort_by(|(_, a), (_, b)| a.position.x.total_cmp(&b.position.x));
+
+        let mut platforms = vec![];
+        let mut enemies = vec![];
+        let mut barrels = vec![];
+        let mut player: Option<(u32, Spawn)> = None;
+
+        let mut end = self.properties.get("end").cloned();
+        // The weapon, and where it was set.
+        let mut weapon = self
+            .properties
+            .get("using_weapon")
+            .map(|weapon| (weapon.clone(), self.position));
+        let mut tuning = Tuning::default();
+
+        tune(&mut tuning, &self.properties, "the map", self.position)?;
+
+        for object in &self.objects {
+            let (line, column) = object.position;
+
+            // Spawn at the centre of the object, tile objects hang up from their position.
+            let y = if object.tile {
+                object.y - object.height / 2.0
+            } else {
+                object.y + object.height / 2.0
+            };
+
+            let spawn = Spawn {
+                position: na::Point2::new(object.x + object.width / 2.0, y),
+                line: 0,
+                column: 0,
+            };
+
+            match object.class.to_lowercase().as_str() {
+                "player" => {
+                    if let Some((first, _)) = player {
+                        return Err(MapError::new(
+                            line,
+                            column,
+                            format!(
+                                "duplicate player in object {}, the first one is object {}",
+                                object.id, first
+                            ),
+                        ));
+                    }
+
+                    player = Some((object.id, spawn));
+                }
+
+                "enemy" => {
+                    let kind = match object.properties.get("kind") {
+                        Some(name) => roster.kind(name).ok_or_else(|| {
+                            MapError::new(
+                                line,
+                                column,
+                                format!("object {} has the unknown kind `{}`", object.id, name),
+                            )
+                        })?,
+                        None => &roster.enemies[0],
+                    };
+
+                    let mut behaviour = kind.behaviour.clone();
+
+                    if let Some(orders) = object.properties.get("behaviour") {
+                        behaviour.set(orders).map_err(|e| {
+                            MapError::new(line, column, format!("object {}: {}", object.id, e))
+                        })?;
+                    }
+
+                    enemies.push((kind.name.clone(), behaviour, spawn));
+                }
+
+                "barrel" => barrels.push(spawn),
+
+                "moving_platform" => {
+                    let motion = match object.properties.get("path") {
+                        Some(path) => PlatformMotion::parse(path).map_err(|e| {
+                            MapError::new(line, column, format!("object {}: {}", object.id, e))
+                        })?,
+                        None => PlatformMotion::default(),
+                    };
+
+                    platforms.push((motion, spawn));
+                }
+
+                "falling_platform" => platforms.push((PlatformMotion::Falling, spawn)),
+
+                // Objects without a class are only there for the designer.
+                "" => {}
+
+                unknown => {
+                    return Err(MapError::new(
+                        line,
+                        column,
+                        format!(
+                        "object {} has the unknown class `{}`, expected player, enemy, barrel, moving_platform or falling_platform",
+                        object.id, unknown
+                    ),
+                    ))
+                }
+            }
+
+            if let Some(quote) = object.properties.get("end") {
+                end = Some(quote.clone());
+            }
+
+            if let Some(using_weapon) = object.properties.get("using_weapon") {
+                weapon = Some((using_weapon.clone(), object.position));
+            }
+
+            tune(
+                &mut tuning,
+                &object.properties,
+                &format!("object {}", object.id),
+                object.position,
+            )?;
+        }
+
+        if let Some((weapon, (line, column))) = weapon
+            .as_ref()
+            .filter(|(weapon, _)| !WEAPONS.contains(&weapon.as_str()))
+        {
+            return Err(MapError::new(
+                *line,
+                *column,
+                format!(
+                    "unknown weapon `{}`, expected one of: {}",
+                    weapon,
+                    WEAPONS.join(", ")
+                ),
+            ));
+        }
+
+        let (_, player) = player.ok_or_else(|| {
+            MapError::new(0, 0, "the map does not contain an object of class `player`")
+        })?;
+
+        // The player dies falling below the lowest tile, without any it would die right away.
+        if tiles.is_empty() {
+            let (line, column) = self.position;
+            return Err(MapError::new(
+                line,
+                column,
+                "the map has no tiles, place some in a tile layer",
+            ));
+        }
+
+        Ok(Level {
+            tiles,
+            platforms,
+            enemies,
+            barrels,
+            player,
+            end,
+            weapon: weapon.map_or_else(|| String::from(WEAPONS[0]), |(weapon, _)| weapon),
+            tuning,
+        })
+    }
+}
+
+/// Apply the tuning properties of the map or of an object, `owner` says which in errors along
+/// with the line and the column of its element.
+fn tune(
+    tuning: &mut Tuning,
+    properties: &HashMap<String, String>,
+    owner: &str,
+    (line, column): (usize, usize),
+) -> Result<(), MapError> {
+    for name in &Tuning::NAMES {
+        if let Some(value) = properties.get(*name) {
+            if let Some(Err(e)) = tuning.set(name, value) {
+                return Err(MapError::new(line, column, format!("{}: {}", owner, e)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The type of tile the class (or type) of the tile with the provided global id picks.
+fn tile_type(gid: u32, class: &str) -> Result<TileType, String> {
+    match class.to_lowercase().as_str() {
+        "left" => Ok(TileType::Left),
+        "center" => Ok(TileType::Center),
+        "right" => Ok(TileType::Right),
+        "one_way" => Ok(TileType::OneWay),
+        "ice" => Ok(TileType::Ice),
+        "bouncy" => Ok(TileType::Bouncy),
+        "spikes" => Ok(TileType::Spikes),
+        _ => Err(format!(
+            "tile {} has the unknown class `{}`, expected left, center, right, one_way, ice, bouncy or spikes",
+            gid, class
+        )),
+    }
+}
+
+fn attribute<'a>(attributes: &'a [OwnedAttribute], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name.local_name == key)
+        .map(|attribute| attribute.value.as_str())
+}
+
+/// A number attribute, 0 if it is missing like Tiled does.
+fn number(attributes: &[OwnedAttribute], key: &str) -> Result<f32, String> {
+    attribute(attributes, key).map_or(Ok(0.0), |value| {
+        value
+            .parse()
+            .map_err(|_| format!("invalid `{}` attribute `{}`", key, value))
+    })
+}
+
+fn properties(properties: Vec<JsonProperty>) -> HashMap<String, String> {
+    properties
+        .into_iter()
+        .map(|property| {
+            let value = match property.value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+
+            (property.name, value)
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+    #[serde(default)]
+    tilesets: Vec<JsonTileset>,
+    layers: Vec<JsonLayer>,
+}
+
+#[derive(Deserialize)]
+struct JsonTileset {
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    tiles: Vec<JsonTile>,
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    width: usize,
+    #[serde(default)]
+    data: serde_json::Value,
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    id: u32,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    value: serde_json::Value,
+}
@@ -0,0 +1,6 @@
+//! # Call of Ferris
+//!
+//! The parts of Call of Ferris that do not need a window or a ggez [`Context`](ggez::Context),
+//! shared between the game and its tools like `ferris-mapcheck`.
+
+pub mod level;
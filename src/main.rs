@@ -4,13 +4,19 @@
 //! Take part in this awesome adventure and help Ferris be the best ever!
 //!
 //! For a fuller outline, see the project's [README.md](https://github.com/Andy-Python-Programmer/CallOfFerris)
+//!
+//! Usage: `call_of_ferris [--endless] [--seed NUMBER]`
+//!
+//! `--endless` skips the menu and starts an endless run of generated levels. `--seed` picks the
+//! seed of the run so that it can be played again, a random one is used otherwise.
 
-use std::{fs, rc::Rc, sync::Mutex};
+use std::{fs, path::Path, rc::Rc, sync::Mutex};
 
 use ggez::{
     conf::WindowMode,
     event::KeyCode,
     event::KeyMods,
+    event::MouseButton,
     graphics::{set_screen_coordinates, Rect},
     Context, ContextBuilder, GameResult,
 };
@@ -18,7 +24,7 @@ use ggez::{
     conf::WindowSetup,
     event::{self, EventHandler},
 };
-use utils::{AssetManager, FerrisResult};
+use utils::{AssetManager, FerrisResult, FileWatcher};
 
 mod screens;
 mod utils;
@@ -35,6 +41,9 @@ const MIN_WIDTH: f32 = 1000.0;
 /// Minimum height.
 const MIN_HEIGHT: f32 = 600.0;
 
+/// How many times a second the screens are updated, whatever the frame rate is.
+pub const UPDATES_PER_SECOND: u32 = 60;
+
 fn init_assets(ctx: &mut Context) -> FerrisResult<AssetManager> {
     let mut asset_manager = AssetManager::new();
 
@@ -59,10 +68,43 @@ fn init_assets(ctx: &mut Context) -> FerrisResult<AssetManager> {
         asset_manager.load_file(ctx, "maps", map?.file_name().to_string_lossy());
     }
 
+    asset_manager.load_roster(ctx)?;
+
     Ok(asset_manager)
 }
 
+/// Parse the command line. Returns the seed of the endless run to start with, if any.
+fn parse_args() -> FerrisResult<Option<u64>> {
+    let mut endless = false;
+    let mut seed = None;
+
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--endless" => endless = true,
+            "--seed" => {
+                let value = args.next().ok_or("--seed expects a number")?;
+                seed = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|e| format!("--seed {}: {}", value, e))?,
+                );
+            }
+            unknown => return Err(format!("unknown argument {}", unknown).into()),
+        }
+    }
+
+    Ok(match (endless, seed) {
+        (true, seed) => Some(seed.unwrap_or_else(rand::random)),
+        (false, Some(_)) => return Err("--seed only makes sense with --endless".into()),
+        (false, None) => None,
+    })
+}
+
 fn main() -> FerrisResult<()> {
+    let endless_seed = parse_args()?;
+
     // The resources directory contains all of the assets.
     // Including sprites and audio files.
     let resource_dir = std::path::PathBuf::from("./resources");
@@ -86,7 +128,11 @@ fn main() -> FerrisResult<()> {
     let asset_manager = init_assets(&mut ctx)?;
 
     // Create an instance of your event handler.
-    let mut game = Game::new(&mut ctx, asset_manager);
+    let mut game = Game::new(&mut ctx, asset_manager)?;
+
+    if let Some(seed) = endless_seed {
+        game.start_endless(&mut ctx, seed)?;
+    }
 
     // Run!
     let exit = event::run(&mut ctx, &mut event_loop, &mut game);
@@ -111,6 +157,10 @@ pub enum Screen {
     Play,
     /// The death screen.
     Dead,
+    /// The level editor screen.
+    Editor,
+    /// The game screen, running an endless run of generated levels.
+    Endless,
 }
 
 /// The current game state.
@@ -123,51 +173,165 @@ pub struct Game {
     game_screen: Mutex<game::Game>,
     /// Reference of the death screen.
     death_screen: dead::Death,
+    /// Mutable reference of the level editor screen.
+    editor_screen: editor::Editor,
+    /// Is the game screen play testing the level from the editor?
+    play_testing: bool,
+    /// The level progression.
+    campaign: game::campaign::Campaign,
+    /// The endless run, used while the current screen is [`Screen::Endless`].
+    endless: game::generator::Endless,
+    /// Watches the maps for changes to reload them while the game is running.
+    map_watcher: FileWatcher,
     /// The asset manager.
     asset_manager: Rc<AssetManager>,
 }
 
 impl Game {
-    pub fn new(ctx: &mut Context, asset_manager: AssetManager) -> Self {
+    pub fn new(ctx: &mut Context, asset_manager: AssetManager) -> GameResult<Self> {
         let asset_manager = Rc::new(asset_manager);
+        let campaign = game::campaign::Campaign::load(ctx, &asset_manager);
 
         // Woah. We are cloning the asset manager. Yes that's why it's wrapped in Rc<>
         // Anything wrapped in a Rc<> and performs a clone it only clones its pointer, so it's fine to use clone here!
-        Self {
+        Ok(Self {
             screen: Screen::Menu,
 
             menu_screen: menu::Menu::create(ctx, asset_manager.clone()),
-            game_screen: game::Game::create(ctx, asset_manager.clone()),
+            game_screen: game::Game::create(
+                ctx,
+                asset_manager.clone(),
+                campaign.current_level(),
+                campaign.has_next(),
+            )?,
             death_screen: dead::Death::spawn(ctx, asset_manager.clone()),
+            editor_screen: editor::Editor::create(
+                ctx,
+                asset_manager.clone(),
+                campaign.current_level(),
+            ),
+            play_testing: false,
+            campaign,
+            endless: game::generator::Endless::new(0),
+            map_watcher: FileWatcher::new("./resources/maps/"),
 
             asset_manager,
+        })
+    }
+
+    /// Recreates the game screen with the current campaign level.
+    fn restart_level(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.game_screen = game::Game::create(
+            ctx,
+            self.asset_manager.clone(),
+            self.campaign.current_level(),
+            self.campaign.has_next(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Starts a new endless run with the provided seed.
+    fn start_endless(&mut self, ctx: &mut Context, seed: u64) -> GameResult<()> {
+        println!(
+            "Starting an endless run, play it again with --endless --seed {}",
+            seed
+        );
+
+        self.endless = game::generator::Endless::new(seed);
+        self.load_endless_stage(ctx)?;
+        self.screen = Screen::Endless;
+
+        Ok(())
+    }
+
+    /// Recreates the game screen with the level of the current stage of the endless run.
+    fn load_endless_stage(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.game_screen = game::Game::from_level(
+            ctx,
+            self.asset_manager.clone(),
+            &self.endless.level(self.asset_manager.roster()),
+            true,
+        )?;
+
+        Ok(())
+    }
+
+    /// Reload the maps changed on disk. If the current level has changed, its world is rebuilt in place.
+    fn reload_maps(&mut self, ctx: &mut Context) {
+        for file in self.map_watcher.poll() {
+            if let Err(e) = self.asset_manager.reload_file(ctx, "maps", &file) {
+                eprintln!("Cannot reload /maps/{}: {}", file, e);
+                continue;
+            }
+
+            // The play test runs the level from the editor and the endless run generated ones,
+            // not the one on disk.
+            let map_id = Path::new(&file).file_stem().unwrap_or_default();
+
+            if self.play_testing
+                || matches!(self.screen, Screen::Endless)
+                || map_id != self.campaign.current_level()
+            {
+                continue;
+            }
+
+            let reloaded = game::load_level(self.campaign.current_level(), &self.asset_manager)
+                .and_then(|level| self.game_screen.lock().unwrap().reload(&level));
+
+            if let Err(e) = reloaded {
+                eprintln!("{}", e);
+            }
         }
     }
+
+    /// Leaves the play test and goes back to the editor.
+    fn end_play_test(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.play_testing = false;
+        self.screen = Screen::Editor;
+
+        self.restart_level(ctx)
+    }
 }
 
 impl EventHandler for Game {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        while ggez::timer::check_update_time(ctx, 60) {
+        // Looking for changed maps once a second is plenty.
+        if ggez::timer::ticks(ctx).is_multiple_of(60) {
+            self.reload_maps(ctx);
+        }
+
+        while ggez::timer::check_update_time(ctx, UPDATES_PER_SECOND) {
             match self.screen {
                 Screen::Menu => self.menu_screen.update(ctx)?,
-                Screen::Play => {
+                Screen::Play | Screen::Endless => {
                     let change = self.game_screen.lock().unwrap().update(ctx)?;
 
                     if let Some(s) = change {
-                        self.screen = s;
+                        if self.play_testing {
+                            self.end_play_test(ctx)?;
+                        } else {
+                            self.screen = s;
+                        }
                     }
                 }
                 Screen::Dead => self.death_screen.update(ctx)?,
+                Screen::Editor => self.editor_screen.update(ctx)?,
             }
         }
 
+        // The music of the level only plays on the game screen.
+        if !matches!(self.screen, Screen::Play | Screen::Endless) {
+            self.game_screen.lock().unwrap().stop_music();
+        }
+
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         match self.screen {
             Screen::Menu => self.menu_screen.draw(ctx),
-            Screen::Play => {
+            Screen::Play | Screen::Endless => {
                 let change = self.game_screen.lock().unwrap().draw(ctx)?;
 
                 if let Some(s) = change {
@@ -177,6 +341,7 @@ impl EventHandler for Game {
                 Ok(())
             }
             Screen::Dead => self.death_screen.draw(ctx),
+            Screen::Editor => self.editor_screen.draw(ctx),
         }
     }
 
@@ -191,31 +356,127 @@ impl EventHandler for Game {
             Screen::Menu => {
                 let change = self.menu_screen.key_press(keycode);
 
-                if let Some(s) = change {
+                if let Some(Screen::Endless) = change {
+                    if let Err(e) = self.start_endless(ctx, rand::random()) {
+                        eprintln!("Cannot start the endless run: {}", e);
+                    }
+                } else if let Some(Screen::Editor) = change {
+                    // The campaign may have moved on since the editor was last opened.
+                    self.editor_screen = editor::Editor::create(
+                        ctx,
+                        self.asset_manager.clone(),
+                        self.campaign.current_level(),
+                    );
+                    self.screen = Screen::Editor;
+                } else if let Some(s) = change {
                     self.screen = s;
                 }
             }
-            Screen::Play => {
+            Screen::Play | Screen::Endless => {
                 let change = self.game_screen.lock().unwrap().key_press(keycode);
 
                 if let Some(s) = change {
-                    if let Screen::Menu = s {
-                        self.game_screen = game::Game::create(ctx, self.asset_manager.clone());
+                    if self.play_testing {
+                        if let Err(e) = self.end_play_test(ctx) {
+                            eprintln!("Cannot load level {}: {}", self.campaign.current_level(), e);
+                        }
+
+                        return;
+                    }
+
+                    // Winning a stage of the endless run moves on to the next, harder, one.
+                    if let (Screen::Endless, Screen::Play) = (&self.screen, &s) {
+                        self.endless.stage += 1;
+
+                        if let Err(e) = self.load_endless_stage(ctx) {
+                            eprintln!("Cannot generate the next stage: {}", e);
+                            self.screen = Screen::Menu;
+                        }
+
+                        return;
+                    }
+
+                    if let Screen::Play = s {
+                        if let Err(e) = self.campaign.advance(ctx) {
+                            eprintln!("Cannot save the campaign progress: {}", e);
+                        }
+                    }
+
+                    // Both going back to the menu and moving on to the next level need a fresh game screen.
+                    // A broken level sends the player back to the menu instead of crashing the game.
+                    match self.restart_level(ctx) {
+                        Ok(()) => self.screen = s,
+                        Err(e) => {
+                            eprintln!("Cannot load level {}: {}", self.campaign.current_level(), e);
+                            self.screen = Screen::Menu;
+                        }
                     }
-                    self.screen = s;
                 }
             }
             Screen::Dead => {}
+            Screen::Editor => {
+                let change = self.editor_screen.key_press(keycode);
+
+                if let Some(s) = change {
+                    if let Screen::Play = s {
+                        let game_screen = self.editor_screen.level().map_err(|e| e.to_string());
+                        let game_screen = game_screen.and_then(|level| {
+                            game::Game::from_level(ctx, self.asset_manager.clone(), &level, false)
+                                .map_err(|e| e.to_string())
+                        });
+
+                        match game_screen {
+                            Ok(game_screen) => {
+                                self.game_screen = game_screen;
+                                self.play_testing = true;
+                            }
+                            Err(e) => {
+                                eprintln!("Cannot play test the level: {}", e);
+                                return;
+                            }
+                        }
+                    }
+
+                    self.screen = s;
+                }
+            }
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if let Screen::Editor = self.screen {
+            self.editor_screen.mouse_motion(ctx, x, y);
+        }
+    }
+
+    fn mouse_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        button: MouseButton,
+        _x: f32,
+        _y: f32,
+    ) {
+        if let Screen::Editor = self.screen {
+            self.editor_screen.mouse_press(button);
+        }
+    }
+
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
+        if let Screen::Editor = self.screen {
+            self.editor_screen.mouse_wheel(x, y);
         }
     }
 
     fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
-        if let Screen::Play = self.screen {
+        if let Screen::Play | Screen::Endless = self.screen {
             self.game_screen.lock().unwrap().key_up_event(keycode)
         }
     }
 
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
         set_screen_coordinates(ctx, Rect::new(0.0, 0.0, width, height)).unwrap();
+
+        self.game_screen.lock().unwrap().resize(width, height);
+        self.editor_screen.resize(width, height);
     }
 }
@@ -0,0 +1,407 @@
+use std::{fs, rc::Rc};
+
+use ggez::{
+    event::{KeyCode, MouseButton},
+    graphics::{self, Color, DrawParam, Scale, Text, TextFragment},
+    input::keyboard,
+    nalgebra::Point2,
+    Context, GameResult,
+};
+use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
+
+use call_of_ferris::level::{
+    glyph_layout, Level, MapError, MapSource, PlatformMotion, TileType, GLYPHS, LEVEL_EXTENSIONS,
+    ROW_HEIGHT, SPAWN_OFFSET,
+};
+
+use crate::{
+    game::{Platform, Tile},
+    utils::AssetManager,
+    Screen,
+};
+
+/// The keys that select the glyphs of `GLYPHS`, in the same order, and their names.
+const PALETTE_KEYS: [(KeyCode, &str); 13] = [
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Minus, "-"),
+    (KeyCode::Equals, "="),
+    (KeyCode::Back, "Bksp"),
+];
+
+/// The level editor. Places glyphs with the mouse and saves the map back as .map text.
+pub struct Editor {
+    asset_manager: Rc<AssetManager>,
+    /// Camera to see the map being edited.
+    camera: Camera,
+
+    /// Id of the map being edited.
+    map_id: String,
+    /// The map being edited.
+    source: MapSource,
+    /// The file of the map when it was made with Tiled. Levels are looked up as .map first, so
+    /// saving one would hide the Tiled map from the game.
+    tiled_file: Option<String>,
+
+    /// Index in `GLYPHS` of the glyph placed with the mouse.
+    selected: usize,
+    /// Index in the roster of the kind of enemy the `8` of the palette places.
+    enemy: usize,
+    /// The column and the height under the mouse. The column is one past the last to append.
+    hover: Option<(usize, i32)>,
+    /// Result of the last check or save, shown at the bottom of the screen.
+    status: String,
+}
+
+impl Editor {
+    /// How fast the arrow keys scroll the camera, in world units per second.
+    const SCROLL_SPEED: f32 = 600.0;
+
+    pub fn create(ctx: &mut Context, asset_manager: Rc<AssetManager>, map_id: &str) -> Self {
+        let (width, height) = graphics::drawable_size(ctx);
+
+        // Levels made with Tiled have no .map file, editing them starts a new one from scratch.
+        let path = format!("/maps/{}.map", map_id);
+        let source = if asset_manager.has_file(&path) {
+            MapSource::from_text(&asset_manager.get_file(&path))
+        } else {
+            MapSource::default()
+        };
+
+        let tiled_file = LEVEL_EXTENSIONS
+            .iter()
+            .map(|extension| format!("{}.{}", map_id, extension))
+            .find(|file| asset_manager.has_file(&format!("/maps/{}", file)))
+            .filter(|file| !file.ends_with(".map"));
+
+        let mut camera = Camera::new(width as u32, height as u32, width, height);
+        camera.move_to(Vec2::new(width / 2.0 - 100.0, -100.0));
+
+        let mut editor = Self {
+            asset_manager,
+            camera,
+
+            map_id: map_id.to_string(),
+            source,
+            tiled_file,
+
+            selected: 0,
+            enemy: 0,
+            hover: None,
+            status: String::new(),
+        };
+
+        editor.check();
+        editor
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let (_, height) = graphics::drawable_size(ctx);
+
+        let consolas = self.asset_manager.get_font("Consolas.ttf");
+
+        graphics::clear(ctx, Color::from_rgb(24, 24, 24));
+
+        let positions = self.source.layout();
+
+        for (id, &(glyph, row)) in self.source.columns.iter().enumerate() {
+            let offset = glyph_layout(glyph).map_or(0.0, |(offset, _)| offset);
+            let tile_position = Vec2::new(positions[id] + offset, -(row as f32) * ROW_HEIGHT);
+
+            let tile = match glyph {
+                '>' => Some(Platform::look(&PlatformMotion::default())),
+                'v' => Some(Platform::look(&PlatformMotion::Falling)),
+                _ => TileType::of_glyph(glyph).map(Tile::look),
+            };
+
+            let kind = self.asset_manager.roster().kind_of_glyph(glyph);
+
+            let sprite = match (kind, glyph) {
+                (Some(kind), _) => {
+                    let [r, g, b] = kind.tint;
+                    Some((kind.sprite.as_str(), Color::from_rgb(r, g, b)))
+                }
+                (None, '4') => Some(("Some(ferris).png", graphics::WHITE)),
+                (None, '*') => Some(("Some(barrel).png", graphics::WHITE)),
+                _ => None,
+            };
+
+            // Enemies stand on a center tile.
+            let tile = tile.or_else(|| kind.map(|_| Tile::look(TileType::Center)));
+
+            if let Some((tile, tint)) = tile {
+                self.draw_image(ctx, tile, tile_position, tint)?;
+            }
+
+            if let Some((sprite, tint)) = sprite {
+                self.draw_image(
+                    ctx,
+                    sprite,
+                    Vec2::new(tile_position.x, tile_position.y - SPAWN_OFFSET),
+                    tint,
+                )?;
+            }
+
+            // Make unknown glyphs stand out so that they can be removed.
+            if glyph_layout(glyph).is_none() && kind.is_none() {
+                self.draw_cell(ctx, positions[id], row, Color::from_rgb(255, 80, 76))?;
+            }
+        }
+
+        if let Some((column, row)) = self.hover {
+            self.draw_cell(ctx, positions[column], row, graphics::WHITE)?;
+        }
+
+        // The glyph palette
+        let mut palette = Text::default();
+
+        for (id, (_, key)) in PALETTE_KEYS.iter().enumerate() {
+            let glyph = self.glyph(id);
+            let color = if id == self.selected {
+                Color::from_rgb(214, 208, 132)
+            } else {
+                graphics::WHITE
+            };
+
+            palette.add(
+                TextFragment::new(format!("{} {}   ", key, glyph))
+                    .font(consolas)
+                    .scale(Scale::uniform(20.0))
+                    .color(color),
+            );
+        }
+
+        graphics::draw(
+            ctx,
+            &palette,
+            DrawParam::default().dest(Point2::new(20.0, 20.0)),
+        )?;
+
+        let editing = match &self.tiled_file {
+            Some(file) => format!("Editing a new map, {} is made with Tiled", file),
+            None => format!("Editing {}.map", self.map_id),
+        };
+
+        let help = Text::new(
+            TextFragment::new(format!(
+                "{} | Click to place, right click to remove, arrows or wheel to scroll | 8 again for another enemy | P to play test, S to save, Esc for the menu",
+                editing
+            ))
+            .font(consolas)
+            .scale(Scale::uniform(15.0)),
+        );
+
+        graphics::draw(
+            ctx,
+            &help,
+            DrawParam::default().dest(Point2::new(20.0, height - 60.0)),
+        )?;
+
+        let status = Text::new(
+            TextFragment::new(self.status.as_str())
+                .font(consolas)
+                .scale(Scale::uniform(15.0))
+                .color(Color::from_rgb(214, 208, 132)),
+        );
+
+        graphics::draw(
+            ctx,
+            &status,
+            DrawParam::default().dest(Point2::new(20.0, height - 35.0)),
+        )?;
+
+        graphics::present(ctx)
+    }
+
+    pub fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let scroll = Self::SCROLL_SPEED / crate::UPDATES_PER_SECOND as f32;
+
+        if keyboard::is_key_pressed(ctx, KeyCode::Left) {
+            self.camera.move_by(Vec2::new(-scroll, 0.0));
+        } else if keyboard::is_key_pressed(ctx, KeyCode::Right) {
+            self.camera.move_by(Vec2::new(scroll, 0.0));
+        }
+
+        if keyboard::is_key_pressed(ctx, KeyCode::Up) {
+            self.camera.move_by(Vec2::new(0.0, -scroll));
+        } else if keyboard::is_key_pressed(ctx, KeyCode::Down) {
+            self.camera.move_by(Vec2::new(0.0, scroll));
+        }
+
+        Ok(())
+    }
+
+    pub fn key_press(&mut self, keycode: KeyCode) -> Option<Screen> {
+        if let Some(id) = PALETTE_KEYS.iter().position(|(key, _)| *key == keycode) {
+            // Selecting the enemy again goes through the kinds of enemies.
+            if id == self.selected && GLYPHS[id] == '8' {
+                self.enemy = (self.enemy + 1) % self.asset_manager.roster().enemies.len();
+            }
+
+            self.selected = id;
+        }
+
+        match keycode {
+            KeyCode::P => match self.level() {
+                Ok(_) => return Some(Screen::Play),
+                Err(e) => self.status = format!("Cannot play test, {}", e),
+            },
+            KeyCode::S => self.save(),
+            KeyCode::Escape => return Some(Screen::Menu),
+            _ => (),
+        }
+
+        None
+    }
+
+    pub fn mouse_motion(&mut self, ctx: &mut Context, x: f32, y: f32) {
+        let (width, height) = graphics::drawable_size(ctx);
+
+        // The camera maps the world to the screen one to one, centered on its location.
+        let location = self.camera.location();
+        let world = Vec2::new(x - width / 2.0 + location.x, y - height / 2.0 + location.y);
+
+        let positions = self.source.layout();
+        let last = self.source.columns.len();
+
+        // A column spans from the left edge of its tile to the left edge of the next one.
+        let edge = |id: usize| positions[id] - ROW_HEIGHT / 2.0;
+
+        self.hover = if world.x < edge(0) {
+            None
+        } else {
+            let column = (0..last).find(|id| world.x < edge(id + 1)).unwrap_or(last);
+
+            Some((column, (-world.y / ROW_HEIGHT).round() as i32))
+        };
+    }
+
+    pub fn mouse_press(&mut self, button: MouseButton) {
+        let (column, row) = match self.hover {
+            Some(hover) => hover,
+            None => return,
+        };
+
+        let glyph = self.glyph(self.selected);
+        let columns = &mut self.source.columns;
+
+        match button {
+            MouseButton::Left => {
+                let cell = (glyph, row);
+
+                if column < columns.len() {
+                    columns[column] = cell;
+                } else {
+                    columns.push(cell);
+                }
+            }
+            MouseButton::Right => {
+                if column < columns.len() {
+                    self.source.remove(column);
+                }
+            }
+            _ => return,
+        }
+
+        self.check();
+    }
+
+    pub fn mouse_wheel(&mut self, x: f32, y: f32) {
+        self.camera.move_by(Vec2::new((x - y) * ROW_HEIGHT, 0.0));
+    }
+
+    /// The window was resized. Keep the camera where it is but map the world to the new screen size.
+    pub fn resize(&mut self, width: f32, height: f32) {
+        let location = self.camera.location();
+
+        self.camera = Camera::new(width as u32, height as u32, width, height);
+        self.camera.move_to(location);
+    }
+
+    /// The glyph the palette places with its provided index, the one of the selected kind of
+    /// enemy for the `8`.
+    fn glyph(&self, id: usize) -> char {
+        match GLYPHS[id] {
+            '8' => self.asset_manager.roster().enemies[self.enemy].glyph,
+            glyph => glyph,
+        }
+    }
+
+    /// Parse the map being edited, used to play test it.
+    pub fn level(&self) -> Result<Level, MapError> {
+        Level::parse(&self.source.to_text(), self.asset_manager.roster())
+    }
+
+    /// Check the map and show the result in the status line.
+    fn check(&mut self) {
+        self.status = match self.level() {
+            Ok(level) => match level.lint().first() {
+                Some(warning) => format!("Warning: {}", warning),
+                None => String::from("No problems found"),
+            },
+            Err(e) => format!("Error: {}", e),
+        };
+    }
+
+    fn save(&mut self) {
+        if let Some(file) = &self.tiled_file {
+            self.status = format!(
+                "Cannot save, a {}.map would be loaded instead of {}. Edit it with Tiled",
+                self.map_id, file
+            );
+            return;
+        }
+
+        let path = format!("./resources/maps/{}.map", self.map_id);
+
+        self.status = match fs::write(&path, self.source.to_text()) {
+            Ok(()) => format!("Saved to {}", path),
+            Err(e) => format!("Cannot save to {}: {}", path, e),
+        };
+    }
+
+    fn draw_image(
+        &self,
+        ctx: &mut Context,
+        image: &str,
+        position: Vec2,
+        tint: Color,
+    ) -> GameResult<()> {
+        let image = self.asset_manager.get_image(image);
+        let position = self.camera.calculate_dest_point(position);
+
+        graphics::draw(
+            ctx,
+            &image,
+            DrawParam::default()
+                .dest(Point2::new(position.x, position.y))
+                .offset(Point2::new(0.5, 0.5))
+                .color(tint),
+        )
+    }
+
+    /// Outline the cell of a column at the provided height.
+    fn draw_cell(&self, ctx: &mut Context, x: f32, row: i32, color: Color) -> GameResult<()> {
+        let half = ROW_HEIGHT / 2.0;
+        let position = self
+            .camera
+            .calculate_dest_point(Vec2::new(x - half, -(row as f32) * ROW_HEIGHT - half));
+
+        let cell = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::stroke(2.0),
+            graphics::Rect::new(position.x, position.y, ROW_HEIGHT, ROW_HEIGHT),
+            color,
+        )?;
+
+        graphics::draw(ctx, &cell, DrawParam::default())
+    }
+}
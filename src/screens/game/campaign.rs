@@ -0,0 +1,82 @@
+//! The campaign is the ordered list of levels Ferris has to go through.
+//!
+//! The order is read from `resources/maps/campaign.list`, one map id per line.
+//! Lines starting with `.comment` are ignored just like in the .map files.
+//!
+//! The id of the level the player is currently on is saved in the user data directory,
+//! so that the progress is remembered between runs.
+
+use std::io::{Read, Write};
+
+use ggez::{filesystem, Context, GameResult};
+
+use crate::utils::AssetManager;
+
+pub struct Campaign {
+    /// The ordered map ids.
+    levels: Vec<String>,
+    /// Index of the level the player is currently on.
+    current: usize,
+}
+
+impl Campaign {
+    /// The file where the progress is saved to. Lives in the user data directory.
+    const PROGRESS_FILE: &'static str = "/progress";
+
+    /// Loads the level order and restores the saved progress if there is any.
+    pub fn load(ctx: &mut Context, asset_manager: &AssetManager) -> Self {
+        let levels = asset_manager
+            .get_file("/maps/campaign.list")
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(".comment"))
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        assert!(
+            !levels.is_empty(),
+            "The campaign does not contain any levels"
+        );
+
+        let mut saved = String::new();
+
+        if let Ok(mut file) = filesystem::open(ctx, Self::PROGRESS_FILE) {
+            // A broken progress file is not worth crashing over. Just start from the beginning.
+            let _ = file.read_to_string(&mut saved);
+        }
+
+        let current = levels
+            .iter()
+            .position(|level| level == saved.trim())
+            .unwrap_or(0);
+
+        Self { levels, current }
+    }
+
+    /// The map id of the current level.
+    pub fn current_level(&self) -> &str {
+        &self.levels[self.current]
+    }
+
+    /// Returns true if there is a level after the current one.
+    pub fn has_next(&self) -> bool {
+        self.current + 1 < self.levels.len()
+    }
+
+    /// Move on to the next level and save the progress.
+    pub fn advance(&mut self, ctx: &mut Context) -> GameResult<()> {
+        if self.has_next() {
+            self.current += 1;
+        }
+
+        self.save(ctx)
+    }
+
+    fn save(&self, ctx: &mut Context) -> GameResult<()> {
+        let mut file = filesystem::create(ctx, Self::PROGRESS_FILE)?;
+
+        file.write_all(self.current_level().as_bytes())?;
+
+        Ok(())
+    }
+}
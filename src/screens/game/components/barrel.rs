@@ -1,36 +1,35 @@
-use ggez::{audio::SoundSource, graphics, nalgebra::Point2, Context, GameResult};
+use ggez::{graphics, nalgebra::Point2, Context, GameResult};
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 use graphics::DrawParam;
 
 use crate::{
-    game::physics::{isometry_to_point, Physics},
-    play,
+    game::physics::{isometry_to_point, BodySpec, Contact, ObjectData, Physics, Shape},
     utils::{AssetManager, ParticleSystem},
 };
 
-use nphysics2d::{nalgebra as na, object::DefaultBodyHandle};
+use rapier2d::{dynamics::RigidBodyHandle, na};
 
-use super::{bullet::PlayerWeapon, player::Player};
+use super::player::Player;
 
 pub struct Barrel {
-    body: DefaultBodyHandle,
+    body: RigidBodyHandle,
 }
 
 impl Barrel {
     pub fn new(
-        ctx: &mut Context,
         pos_x: f32,
+        pos_y: f32,
         physics: &mut Physics,
         asset_manager: &AssetManager,
     ) -> Self {
-        let (_, height) = graphics::drawable_size(ctx);
-
-        let barrel = asset_manager.get_image("Some(barrel).png");
-
-        let body = physics.create_barrel(
-            na::Point2::new(pos_x, height / 2.0 - 155.0),
-            barrel.width(),
-            barrel.height(),
+        let body = physics.create_body(
+            BodySpec::dynamic(
+                ObjectData::Barrel,
+                Shape::sprite(asset_manager.image_size("Some(barrel).png")),
+            )
+            .position(na::Point2::new(pos_x, pos_y))
+            .mass(10.0)
+            .damping(1.0),
         );
 
         Self { body }
@@ -45,7 +44,7 @@ impl Barrel {
     ) -> GameResult<()> {
         let barrel = asset_manager.get_image("Some(barrel).png");
 
-        let barrel_position = self.position(physics);
+        let barrel_position = physics.interpolated_position(self.body);
         let barrel_pos_camera =
             camera.calculate_dest_point(Vec2::new(barrel_position.x, barrel_position.y));
 
@@ -66,44 +65,39 @@ impl Barrel {
         asset_manager: &AssetManager,
         particles: &mut Vec<ParticleSystem>,
         player: &mut Player,
+        contacts: &[Contact],
     ) -> bool {
-        let barrel = asset_manager.get_image("Some(barrel).png");
+        let (width, height) = asset_manager.image_size("Some(barrel).png");
 
         let position = self.position(physics);
 
-        for i in 0..player.weapons.len() {
-            match &mut player.weapons[i] {
-                PlayerWeapon::Turbofish(fish) => {
-                    if fish.is_touching(physics, self.handle()) {
-                        let explode_sound = asset_manager.get_sound("Some(explode).mp3");
-
-                        // FIXME
-                        particles.push(ParticleSystem::new(
-                            physics,
-                            100,
-                            na::Point2::new(
-                                position.x - (barrel.width() / 2) as f32,
-                                position.y - (barrel.height() / 2) as f32,
-                            ),
-                            na::Point2::new(
-                                position.x + (barrel.width() / 2) as f32,
-                                position.y + (barrel.height() / 2) as f32,
-                            ),
-                        ));
-
-                        play!(explode_sound);
-
-                        // Remove the enemy from the world
-                        self.destroy(physics);
-
-                        // Remove the weapon from the world
-                        fish.destroy(physics);
-                        player.weapons.remove(i);
-
-                        return true;
-                    }
-                }
-                PlayerWeapon::Grappling(_) => {}
+        let hit_by = contacts
+            .iter()
+            .find_map(|contact| contact.started_with(self.handle(), ObjectData::Bullet));
+
+        if let Some(bullet) = hit_by {
+            if player.destroy_turbofish(physics, bullet) {
+                // FIXME
+                particles.push(ParticleSystem::new(
+                    physics,
+                    100,
+                    na::Point2::new(
+                        position.x - (width / 2) as f32,
+                        position.y - (height / 2) as f32,
+                    ),
+                    na::Point2::new(
+                        position.x + (width / 2) as f32,
+                        position.y + (height / 2) as f32,
+                    ),
+                    ParticleSystem::FIRE,
+                ));
+
+                asset_manager.play_sound("Some(explode).mp3");
+
+                // Remove the enemy from the world
+                self.destroy(physics);
+
+                return true;
             }
         }
 
@@ -117,7 +111,7 @@ impl Barrel {
         barrel_position
     }
 
-    pub fn handle(&self) -> DefaultBodyHandle {
+    pub fn handle(&self) -> RigidBodyHandle {
         self.body
     }
 
@@ -5,13 +5,17 @@ use ggez::{
 };
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 
-use nphysics2d::{algebra::Velocity2, math::Velocity, nalgebra as na, object::DefaultBodyHandle};
+use rapier2d::{dynamics::RigidBodyHandle, na};
 
 use crate::{
-    game::physics::{isometry_to_point, ObjectData, Physics},
+    game::physics::{
+        isometry_to_point, BodySpec, Contact, ContactKind, Layer, ObjectData, Physics, Shape,
+    },
     utils::AssetManager,
 };
 
+pub use call_of_ferris::level::EnemyWeapon;
+
 pub enum PlayerWeapon {
     Turbofish(Turbofish),
     Grappling(Grappling),
@@ -23,25 +27,33 @@ pub enum WeaponType {
 }
 
 pub struct Turbofish {
-    body: DefaultBodyHandle,
+    body: RigidBodyHandle,
 }
 
 impl Turbofish {
+    /// The health an enemy loses when a turbofish hits it.
+    pub const DAMAGE: i32 = 50;
+
     pub fn new(
         pos_x: f32,
         pos_y: f32,
         physics: &mut Physics,
         asset_manager: &AssetManager,
     ) -> Self {
-        let turbofish_bullet = asset_manager.get_image("Some(turbofish).png");
-        let body = physics.create_bullet(
-            na::Point2::new(pos_x, pos_y),
-            turbofish_bullet.width(),
-            turbofish_bullet.height(),
+        // The bullet is fast enough to go through thin things in a single step without CCD.
+        let body = physics.create_body(
+            BodySpec::dynamic(
+                ObjectData::Bullet,
+                Shape::sprite(asset_manager.image_size("Some(turbofish).png")),
+            )
+            .position(na::Point2::new(pos_x, pos_y))
+            .mass(10.0)
+            .damping(1.0)
+            .ccd(true),
         );
 
         let bullet_body = physics.get_rigid_body_mut(body);
-        bullet_body.set_velocity(Velocity2::linear(1000.0, 0.0));
+        bullet_body.set_linvel(na::Vector2::new(1000.0, 0.0), true);
 
         Self { body }
     }
@@ -55,7 +67,7 @@ impl Turbofish {
     ) -> GameResult<()> {
         let turbofish_bullet = asset_manager.get_image("Some(turbofish).png");
 
-        let bullet_position = self.position(physics);
+        let bullet_position = physics.interpolated_position(self.body);
         let turbofish_position =
             camera.calculate_dest_point(Vec2::new(bullet_position.x, bullet_position.y));
 
@@ -70,61 +82,60 @@ impl Turbofish {
         Ok(())
     }
 
-    pub fn update(&mut self, physics: &mut Physics) -> bool {
-        for collision in physics.collisions(self.body) {
-            if collision.0 .1 == ObjectData::Ground {
-                return true;
-            }
-        }
+    /// Returns true when the bullet hit the ground and has to be destroyed. A bullet that hit an
+    /// enemy or a barrel at the same time is left to them, so that the hit still counts.
+    pub fn update(&mut self, contacts: &[Contact]) -> bool {
+        let started_with = |data| {
+            contacts
+                .iter()
+                .any(|contact| contact.started_with(self.body, data).is_some())
+        };
 
-        false
-    }
+        let hit_target = started_with(ObjectData::Enemy) || started_with(ObjectData::Barrel);
 
-    pub fn is_touching(&mut self, physics: &mut Physics, handle: DefaultBodyHandle) -> bool {
-        for collision in physics.collisions(self.body) {
-            if collision.1 == handle {
-                return true;
-            }
-        }
+        !hit_target && (started_with(ObjectData::Ground) || started_with(ObjectData::Spikes))
+    }
 
-        false
+    pub fn handle(&self) -> RigidBodyHandle {
+        self.body
     }
 
     pub fn destroy(&mut self, physics: &mut Physics) {
         physics.destroy_body(self.body);
     }
-
-    pub fn position(&self, physics: &mut Physics) -> na::Point2<f32> {
-        let bullet_body = physics.get_rigid_body_mut(self.body);
-        let bullet_position = isometry_to_point(bullet_body.position());
-
-        bullet_position
-    }
 }
 
 pub struct Grappling {
-    grapple_to: DefaultBodyHandle,
-    player_body: DefaultBodyHandle,
+    grapple_to: RigidBodyHandle,
+    player_body: RigidBodyHandle,
 }
 
 impl Grappling {
+    /// The health an enemy loses when a barrel pulled by the grappling gun hits it.
+    pub const DAMAGE: i32 = 30;
+
     pub fn new(
         pos_x: f32,
         pos_y: f32,
         physics: &mut Physics,
-        handle: DefaultBodyHandle,
+        handle: RigidBodyHandle,
     ) -> Option<Self> {
-        let ray_cast = physics.ray_cast(na::Point2::new(pos_x, pos_y), na::Vector2::new(1.0, 1.0));
+        let ray_cast = physics.ray_cast(
+            na::Point2::new(pos_x, pos_y),
+            na::Vector2::new(1.0, 1.0),
+            Layer::Ground,
+        );
 
         if !ray_cast.is_empty() {
             for object in ray_cast {
                 if object.0 == ObjectData::Barrel {
-                    let body = object.1.body();
+                    let body = object.1;
                     let body_pos = isometry_to_point(physics.get_rigid_body(body).position());
 
-                    physics
-                        .get_rigid_body_mut(body)
-                        .set_velocity(Velocity::linear(pos_x - body_pos.x, pos_y - body_pos.y));
+                    physics.get_rigid_body_mut(body).set_linvel(
+                        na::Vector2::new(pos_x - body_pos.x, pos_y - body_pos.y),
+                        true,
+                    );
 
                     return Some(Self {
                         grapple_to: body,
@@ -145,7 +156,7 @@ impl Grappling {
         camera: &Camera,
         physics: &mut Physics,
     ) -> GameResult<()> {
-        let player = isometry_to_point(physics.get_rigid_body(self.player_body).position());
+        let player = physics.interpolated_position(self.player_body);
 
         let rect = graphics::Mesh::new_rectangle(
             ctx,
@@ -170,14 +181,99 @@ impl Grappling {
         Ok(())
     }
 
+    /// The body being pulled to the player.
+    pub fn grapple_to(&self) -> RigidBodyHandle {
+        self.grapple_to
+    }
+
     pub fn update(&mut self, physics: &mut Physics) {
         let player = isometry_to_point(physics.get_rigid_body(self.player_body).position());
         let object = isometry_to_point(physics.get_rigid_body(self.grapple_to).position());
 
         if physics.distance(self.player_body, self.grapple_to) as i32 > 1 {
-            physics
-                .get_rigid_body_mut(self.grapple_to)
-                .set_velocity(Velocity::linear(player.x - object.x, player.y - object.y));
+            physics.get_rigid_body_mut(self.grapple_to).set_linvel(
+                na::Vector2::new(player.x - object.x, player.y - object.y),
+                true,
+            );
         }
     }
 }
+
+/// A bullet an enemy shot at the player. Flies straight until it hits something.
+pub struct EnemyBullet {
+    body: RigidBodyHandle,
+    /// When the bullet is gone if it did not hit anything, in the time of the physics world.
+    expires_at: f32,
+}
+
+impl EnemyBullet {
+    pub const RADIUS: f32 = 5.0;
+    /// How long a bullet flies for if it does not hit anything, in seconds.
+    const LIFETIME: f32 = 3.0;
+
+    /// Shoot a bullet of the provided weapon from the provided position, towards the provided
+    /// direction.
+    pub fn new(
+        position: na::Point2<f32>,
+        direction: na::Vector2<f32>,
+        weapon: &EnemyWeapon,
+        physics: &mut Physics,
+    ) -> Self {
+        let body = physics.create_body(
+            BodySpec::dynamic(
+                ObjectData::EnemyBullet(weapon.damage.max(0) as u32),
+                Shape::Ball {
+                    radius: Self::RADIUS,
+                },
+            )
+            .position(position)
+            .gravity_scale(0.0)
+            .ccd(true),
+        );
+
+        physics
+            .get_rigid_body_mut(body)
+            .set_linvel(direction.normalize() * weapon.bullet_speed, true);
+
+        Self {
+            body,
+            expires_at: physics.time() + Self::LIFETIME,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        camera: &Camera,
+        physics: &mut Physics,
+    ) -> GameResult<()> {
+        let bullet_position = physics.interpolated_position(self.body);
+        let bullet_position =
+            camera.calculate_dest_point(Vec2::new(bullet_position.x, bullet_position.y));
+
+        let bullet = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::fill(),
+            Point2::new(bullet_position.x, bullet_position.y),
+            Self::RADIUS,
+            0.5,
+            [1.0, 0.45, 0.2, 1.0].into(),
+        )?;
+
+        graphics::draw(ctx, &bullet, DrawParam::default())
+    }
+
+    /// Destroy the bullet once it hit something, or flew for too long. Returns whether it was.
+    pub fn update(&mut self, physics: &mut Physics, contacts: &[Contact]) -> bool {
+        let hit = contacts.iter().any(|contact| {
+            contact.kind == ContactKind::Started && contact.other(self.body).is_some()
+        });
+
+        if hit || physics.time() >= self.expires_at {
+            physics.destroy_body(self.body);
+            return true;
+        }
+
+        false
+    }
+}
@@ -7,7 +7,7 @@ use graphics::DrawParam;
 
 use crate::utils::AssetManager;
 
-use nphysics2d::nalgebra as na;
+use rapier2d::na;
 
 pub struct Cloud {
     position: na::Point2<f32>,
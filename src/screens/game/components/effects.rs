@@ -0,0 +1,156 @@
+use ggez::{
+    graphics::{self, Color, DrawParam, Font, Scale, Text, TextFragment},
+    nalgebra::Point2,
+    Context, GameResult,
+};
+use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
+use rapier2d::na;
+
+use crate::{game::physics::Physics, utils::ParticleSystem};
+
+/// What is only there to be seen: the particles of the explosions, the damage the enemies take
+/// and the shockwaves of the enemies blowing up.
+#[derive(Default)]
+pub struct Effects {
+    pub particles: Vec<ParticleSystem>,
+    pub damage_numbers: Vec<DamageNumber>,
+    pub shockwaves: Vec<Shockwave>,
+}
+
+impl Effects {
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        camera: &mut Camera,
+        physics: &mut Physics,
+        font: Font,
+    ) -> GameResult<()> {
+        for sys in &mut self.particles {
+            sys.draw(ctx, physics, camera)?;
+        }
+
+        for shockwave in &self.shockwaves {
+            shockwave.draw(ctx, camera, physics)?;
+        }
+
+        for number in &self.damage_numbers {
+            number.draw(ctx, camera, physics, font)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, physics: &mut Physics) {
+        for id in 0..self.particles.len() {
+            let sys = &mut self.particles[id];
+
+            if sys.update(ctx, physics) {
+                self.particles.remove(id);
+
+                break;
+            }
+        }
+
+        let time = physics.time();
+
+        self.damage_numbers
+            .retain(|number| time - number.shown_at < DamageNumber::LIFETIME);
+        self.shockwaves
+            .retain(|shockwave| time - shockwave.started_at < Shockwave::LIFETIME);
+    }
+}
+
+/// The damage something took, floating up from where it was hit and fading out.
+pub struct DamageNumber {
+    damage: i32,
+    position: na::Point2<f32>,
+    /// When the number showed up, in the time of the physics world.
+    shown_at: f32,
+}
+
+impl DamageNumber {
+    /// How long a number stays on the screen, in seconds.
+    const LIFETIME: f32 = 0.8;
+    /// How fast a number floats up, in pixels per second.
+    const RISE: f32 = 80.0;
+
+    pub fn new(damage: i32, position: na::Point2<f32>, physics: &Physics) -> Self {
+        Self {
+            damage,
+            position,
+            shown_at: physics.time(),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        camera: &Camera,
+        physics: &Physics,
+        font: Font,
+    ) -> GameResult<()> {
+        let age = physics.time() - self.shown_at;
+        let fade = 1.0 - age / Self::LIFETIME;
+
+        let number = Text::new(
+            TextFragment::new(format!("-{}", self.damage))
+                .font(font)
+                .scale(Scale::uniform(28.0))
+                .color(Color::new(1.0, 0.9, 0.3, fade)),
+        );
+        let (width, height) = number.dimensions(ctx);
+
+        let position = camera.calculate_dest_point(Vec2::new(
+            self.position.x,
+            self.position.y - age * Self::RISE,
+        ));
+
+        graphics::draw(
+            ctx,
+            &number,
+            DrawParam::default().dest(Point2::new(
+                position.x - width as f32 / 2.0,
+                position.y - height as f32 / 2.0,
+            )),
+        )
+    }
+}
+
+/// A ring growing out of where something blew up.
+pub struct Shockwave {
+    position: na::Point2<f32>,
+    color: Color,
+    /// When it blew up, in the time of the physics world.
+    started_at: f32,
+}
+
+impl Shockwave {
+    /// How long the ring grows for, in seconds.
+    const LIFETIME: f32 = 0.4;
+    /// How big the ring gets.
+    const RADIUS: f32 = 150.0;
+
+    pub fn new(position: na::Point2<f32>, color: Color, physics: &Physics) -> Self {
+        Self {
+            position,
+            color,
+            started_at: physics.time(),
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, camera: &Camera, physics: &Physics) -> GameResult<()> {
+        let progress = (physics.time() - self.started_at) / Self::LIFETIME;
+        let position = camera.calculate_dest_point(Vec2::new(self.position.x, self.position.y));
+
+        let ring = graphics::Mesh::new_circle(
+            ctx,
+            graphics::DrawMode::stroke(12.0 * (1.0 - progress) + 1.0),
+            Point2::new(position.x, position.y),
+            Self::RADIUS * progress + 1.0,
+            0.5,
+            Color::new(self.color.r, self.color.g, self.color.b, 1.0 - progress),
+        )?;
+
+        graphics::draw(ctx, &ring, DrawParam::default())
+    }
+}
@@ -1,43 +1,161 @@
 use ggez::{
-    audio::SoundSource,
-    graphics::{self, DrawParam},
+    graphics::{self, Color, DrawParam, Text, TextFragment},
+    mint::Vector2,
     nalgebra::Point2,
     Context, GameResult,
 };
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 
-use nphysics2d::{nalgebra as na, object::DefaultBodyHandle};
+use rapier2d::{dynamics::RigidBodyHandle, na};
 
 use crate::{
-    game::physics::{isometry_to_point, Physics},
-    play,
+    game::physics::{
+        isometry_to_point, BodySpec, Contact, ContactKind, Layer, ObjectData, Physics, Shape,
+    },
     utils::{AssetManager, ParticleSystem},
 };
 
-use super::{bullet::PlayerWeapon, player::Player};
+pub use call_of_ferris::level::{EnemyBehaviour, EnemyKind};
+
+use super::{
+    bullet::EnemyBullet,
+    effects::{DamageNumber, Effects, Shockwave},
+    player::Player,
+};
+
+/// What an enemy is doing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyState {
+    /// Walks back and forth between the edges of the ground it stands on.
+    Patrol,
+    /// Saw the player and turns to it, since the provided time of the physics world.
+    Alert(f32),
+    /// Shoots at the player, and looks for it for a while once it is out of sight.
+    Attack,
+    /// Runs away from the player, with too little health left to fight.
+    Flee,
+}
+
+/// Decides what an enemy does from what it sees and how much health it has left.
+pub struct EnemyBrain {
+    behaviour: EnemyBehaviour,
+    state: EnemyState,
+
+    /// When the enemy last saw the player, in the time of the physics world.
+    seen_at: f32,
+}
+
+impl EnemyBrain {
+    /// How long an enemy looks for the player it lost sight of before it patrols again, in seconds.
+    const MEMORY: f32 = 2.0;
+
+    pub fn new(behaviour: EnemyBehaviour) -> Self {
+        Self {
+            behaviour,
+            state: EnemyState::Patrol,
+
+            seen_at: f32::NEG_INFINITY,
+        }
+    }
+
+    /// Move on to the next state at the provided time, from whether the enemy sees the player and
+    /// the part of its health, from 0 to 1, it has left.
+    pub fn think(&mut self, time: f32, sees_player: bool, health: f32) -> EnemyState {
+        if sees_player {
+            self.seen_at = time;
+        }
+
+        self.state = match self.state {
+            _ if health < self.behaviour.flee => EnemyState::Flee,
+            EnemyState::Patrol if sees_player => EnemyState::Alert(time),
+            EnemyState::Alert(since) if time - since >= self.behaviour.alert => EnemyState::Attack,
+            EnemyState::Attack if time - self.seen_at > Self::MEMORY => EnemyState::Patrol,
+            state => state,
+        };
+
+        self.state
+    }
+
+    pub fn state(&self) -> EnemyState {
+        self.state
+    }
+
+    pub fn behaviour(&self) -> &EnemyBehaviour {
+        &self.behaviour
+    }
+}
 
 pub struct Enemy {
-    body: DefaultBodyHandle,
+    body: RigidBodyHandle,
+    brain: EnemyBrain,
+    kind: EnemyKind,
+
+    pub health: i32,
+    pub max_health: i32,
+
+    /// When the enemy was last hurt, in the time of the physics world.
+    hurt_at: Option<f32>,
+
+    /// Whether the enemy looks to the left, where its gun points to begin with.
+    facing_left: bool,
+    /// When the enemy can shoot again, in the time of the physics world.
+    reloaded_at: f32,
+    /// Where the enemy last saw the player, to run away from it.
+    player_x: f32,
 }
 
 impl Enemy {
+    /// How much faster than it patrols the enemy runs away.
+    const FLEE_SPEED: f32 = 2.0;
+    /// How far in front of itself the enemy looks for ground to walk on or a wall to turn at.
+    const LOOK_AHEAD: f32 = 10.0;
+    /// How far down the ground in front of the enemy can be for it to walk on.
+    const STEP_DOWN: f32 = 20.0;
+    /// How fast the enemy gets thrown away from what hit it, sideways and up.
+    const KNOCKBACK: (f32, f32) = (250.0, 150.0);
+    /// For how long the enemy is thrown back by a hit before it walks again, in seconds.
+    const STAGGER: f32 = 0.3;
+    /// For how long the enemy flashes red after getting hit, in seconds.
+    const HIT_FLASH: f32 = 0.15;
+    /// The sparks flying off the enemy when it gets hit.
+    const HIT_SPARKS: usize = 8;
+    /// How far below the centre of the enemy its gun is held, at the edge of the enemy it looks to.
+    const GUN_HEIGHT: f32 = 10.0;
+    /// How far down the ground below the enemy can be for it to jump.
+    const JUMP_REACH: f32 = 2.0;
+
+    /// Spawn an enemy of the provided kind, behaving as provided instead of as the kind does.
     pub fn new(
-        ctx: &mut Context,
         pos_x: f32,
+        pos_y: f32,
         physics: &mut Physics,
         asset_manager: &AssetManager,
+        kind: &EnemyKind,
+        behaviour: EnemyBehaviour,
     ) -> Self {
-        let (_, height) = graphics::drawable_size(ctx);
+        let body = physics.create_body(
+            BodySpec::dynamic(
+                ObjectData::Enemy,
+                Shape::sprite(asset_manager.image_size(&kind.sprite)),
+            )
+            .position(na::Point2::new(pos_x, pos_y))
+            .mass(10.0)
+            .damping(1.0),
+        );
 
-        let gopher = asset_manager.get_image("gopher.png");
+        Self {
+            body,
+            brain: EnemyBrain::new(behaviour),
+            kind: kind.clone(),
 
-        let body = physics.create_enemy(
-            na::Point2::new(pos_x, height / 2.0 - 155.0),
-            gopher.width(),
-            gopher.height(),
-        );
+            health: kind.health,
+            max_health: kind.health,
+            hurt_at: None,
 
-        Self { body }
+            facing_left: true,
+            reloaded_at: 0.0,
+            player_x: pos_x,
+        }
     }
 
     pub fn draw(
@@ -47,31 +165,73 @@ impl Enemy {
         physics: &mut Physics,
         asset_manager: &AssetManager,
     ) -> GameResult<()> {
-        let gopher = asset_manager.get_image("gopher.png");
-        let gun = asset_manager.get_image("Some(gun).png");
+        let gopher = asset_manager.get_image(&self.kind.sprite);
+        let [r, g, b] = self.kind.tint;
 
-        let enemy_position = self.position(physics);
+        // The enemy flashes red when it gets hit.
+        let tint = match self.hurt_at {
+            Some(hurt_at) if physics.time() - hurt_at < Self::HIT_FLASH => {
+                Color::from_rgb(255, 90, 90)
+            }
+            _ => Color::from_rgb(r, g, b),
+        };
+
+        let enemy_position = physics.interpolated_position(self.body);
         let gopher_position =
             camera.calculate_dest_point(Vec2::new(enemy_position.x, enemy_position.y));
 
+        // The images look to the left, they are mirrored to look to the right.
+        let (mirror, scale) = if self.facing_left {
+            (1.0, Vector2 { x: 1.0, y: 1.0 })
+        } else {
+            (-1.0, Vector2 { x: -1.0, y: 1.0 })
+        };
+
         graphics::draw(
             ctx,
             &gopher,
             DrawParam::default()
                 .dest(Point2::new(gopher_position.x, gopher_position.y))
-                .offset(Point2::new(0.5, 0.5)),
+                .offset(Point2::new(0.5, 0.5))
+                .scale(scale)
+                .color(tint),
         )?;
 
-        graphics::draw(
-            ctx,
-            &gun,
-            DrawParam::default()
-                .dest(Point2::new(
-                    gopher_position.x - 50.0,
-                    gopher_position.y + 10.0,
-                ))
-                .offset(Point2::new(0.5, 0.5)),
-        )?;
+        if let Some(weapon) = &self.kind.weapon {
+            let gun = asset_manager.get_image(&weapon.sprite);
+
+            graphics::draw(
+                ctx,
+                &gun,
+                DrawParam::default()
+                    .dest(Point2::new(
+                        gopher_position.x - mirror * gopher.width() as f32 / 2.0,
+                        gopher_position.y + Self::GUN_HEIGHT,
+                    ))
+                    .offset(Point2::new(0.5, 0.5))
+                    .scale(scale),
+            )?;
+        }
+
+        // An alert enemy has an exclamation mark above its head.
+        if let EnemyState::Alert(_) = self.brain.state() {
+            let mark = Text::new(
+                TextFragment::new("!")
+                    .font(asset_manager.get_font("Consolas.ttf"))
+                    .scale(graphics::Scale::uniform(40.0))
+                    .color(Color::from_rgb(255, 220, 60)),
+            );
+            let (mark_width, mark_height) = mark.dimensions(ctx);
+
+            graphics::draw(
+                ctx,
+                &mark,
+                DrawParam::default().dest(Point2::new(
+                    gopher_position.x - mark_width as f32 / 2.0,
+                    gopher_position.y - gopher.height() as f32 / 2.0 - mark_height as f32,
+                )),
+            )?;
+        }
 
         Ok(())
     }
@@ -80,55 +240,248 @@ impl Enemy {
         &mut self,
         physics: &mut Physics,
         asset_manager: &AssetManager,
-        particles: &mut Vec<ParticleSystem>,
+        effects: &mut Effects,
         player: &mut Player,
+        bullets: &mut Vec<EnemyBullet>,
+        contacts: &[Contact],
     ) -> bool {
         let position = self.position(physics);
 
-        let gopher = asset_manager.get_image("gopher.png");
-        let explode_sound = asset_manager.get_sound("Some(explode).mp3");
-
-        for i in 0..player.weapons.len() {
-            match &mut player.weapons[i] {
-                PlayerWeapon::Turbofish(fish) => {
-                    if fish.is_touching(physics, self.handle()) {
-                        particles.push(ParticleSystem::new(
-                            physics,
-                            50,
-                            na::Point2::new(
-                                position.x - (gopher.width() / 2) as f32,
-                                position.y - (gopher.height() / 2) as f32,
-                            ),
-                            na::Point2::new(
-                                position.x + (gopher.width() / 2) as f32,
-                                position.y + (gopher.height() / 2) as f32,
-                            ),
-                        ));
-
-                        play!(explode_sound);
-
-                        // Remove the enemy from the world
-                        self.destroy(physics);
-
-                        // Remove the weapon from the world
-                        fish.destroy(physics);
-                        player.weapons.remove(i);
-
-                        return true;
-                    }
+        let (width, height) = asset_manager.image_size(&self.kind.sprite);
+        let min = na::Point2::new(
+            position.x - (width / 2) as f32,
+            position.y - (height / 2) as f32,
+        );
+        let max = na::Point2::new(
+            position.x + (width / 2) as f32,
+            position.y + (height / 2) as f32,
+        );
+
+        // Only the player's weapons hurt the enemies.
+        for contact in contacts {
+            let other = match contact.other(self.body) {
+                Some((other, ObjectData::Bullet)) | Some((other, ObjectData::Barrel))
+                    if contact.kind == ContactKind::Started =>
+                {
+                    other
                 }
-                PlayerWeapon::Grappling(_) => {}
+                _ => continue,
+            };
+
+            let from = isometry_to_point(physics.get_rigid_body(other).position());
+
+            if let Some(damage) = player.hit_with(physics, other) {
+                self.health -= damage;
+                self.hurt_at = Some(physics.time());
+
+                effects.damage_numbers.push(DamageNumber::new(
+                    damage,
+                    na::Point2::new(position.x, min.y),
+                    physics,
+                ));
+                effects.particles.push(ParticleSystem::new(
+                    physics,
+                    Self::HIT_SPARKS,
+                    min,
+                    max,
+                    [[255, 255, 255], [255, 230, 150]],
+                ));
+
+                // Thrown away from what hit it, and a bit up.
+                let side = if from.x < position.x { 1.0 } else { -1.0 };
+                let enemy_body = physics.get_rigid_body_mut(self.body);
+                let impulse = na::Vector2::new(side * Self::KNOCKBACK.0, -Self::KNOCKBACK.1)
+                    * enemy_body.mass();
+
+                enemy_body.apply_impulse(impulse, true);
             }
         }
 
-        // Can the enemy see the player?
-        if physics.distance(self.handle(), player.handle()) < 300.0 {
-            // TODO: The enemy shoots the player as soon as it sees the player.
+        if self.health <= 0 {
+            effects.particles.push(ParticleSystem::new(
+                physics,
+                self.kind.explosion.particles,
+                min,
+                max,
+                self.kind.explosion.colors,
+            ));
+
+            let [r, g, b] = self.kind.explosion.colors[1];
+            effects
+                .shockwaves
+                .push(Shockwave::new(position, Color::from_rgb(r, g, b), physics));
+
+            asset_manager.play_sound("Some(explode).mp3");
+
+            // Remove the enemy from the world
+            self.destroy(physics);
+
+            return true;
+        }
+
+        let seen = self.sees(physics, player);
+
+        if let Some(player_position) = seen {
+            self.player_x = player_position.x;
+        }
+
+        let state = self.brain.think(
+            physics.time(),
+            seen.is_some(),
+            self.health as f32 / self.max_health as f32,
+        );
+        let speed = self.brain.behaviour().speed;
+
+        let walk_speed = match state {
+            EnemyState::Patrol if speed > 0.0 => {
+                // Turn around at the edges and the walls, unless there is nowhere to go either way.
+                if !self.can_walk(physics, asset_manager, self.facing_left)
+                    && self.can_walk(physics, asset_manager, !self.facing_left)
+                {
+                    self.facing_left = !self.facing_left;
+                }
+
+                speed
+            }
+
+            EnemyState::Patrol => 0.0,
+
+            EnemyState::Alert(_) => {
+                self.facing_left = self.player_x < position.x;
+                0.0
+            }
+
+            EnemyState::Attack => {
+                self.facing_left = self.player_x < position.x;
+
+                match (&self.kind.weapon, seen) {
+                    (Some(weapon), Some(player_position)) => {
+                        if physics.time() >= self.reloaded_at {
+                            // The bullets come out of the end of the gun.
+                            let (gun_width, _) = asset_manager.image_size(&weapon.sprite);
+                            let mirror = if self.facing_left { -1.0 } else { 1.0 };
+                            let muzzle = na::Point2::new(
+                                position.x + mirror * (width + gun_width) as f32 / 2.0,
+                                position.y + Self::GUN_HEIGHT,
+                            );
+
+                            bullets.push(EnemyBullet::new(
+                                muzzle,
+                                player_position - muzzle,
+                                weapon,
+                                physics,
+                            ));
+                            self.reloaded_at = physics.time() + self.brain.behaviour().reload;
+                        }
+
+                        0.0
+                    }
+
+                    // Without a gun the enemy runs into the player.
+                    (None, _) => speed,
+
+                    (Some(_), None) => 0.0,
+                }
+            }
+
+            EnemyState::Flee => {
+                self.facing_left = self.player_x > position.x;
+                speed * Self::FLEE_SPEED
+            }
+        };
+
+        // The enemy stops at the edges rather than falling off them.
+        let walk_speed = if self.can_walk(physics, asset_manager, self.facing_left) {
+            walk_speed
+        } else {
+            0.0
+        };
+
+        // The enemies that jump hop along as they walk.
+        let jumps = self.kind.jump > 0.0
+            && walk_speed > 0.0
+            && Self::distance_to_ground(physics, position, na::Vector2::new(0.0, 1.0))
+                .is_some_and(|distance| distance <= height as f32 / 2.0 + Self::JUMP_REACH);
+
+        // The enemy does not walk while it is thrown back by a hit.
+        if self
+            .hurt_at
+            .is_some_and(|hurt_at| physics.time() - hurt_at < Self::STAGGER)
+        {
+            return false;
         }
 
+        let direction = if self.facing_left { -1.0 } else { 1.0 };
+        let enemy_body = physics.get_rigid_body_mut(self.body);
+        let velocity = *enemy_body.linvel();
+        let velocity_y = if jumps { -self.kind.jump } else { velocity.y };
+
+        enemy_body.set_linvel(na::Vector2::new(direction * walk_speed, velocity_y), true);
+
         false
     }
 
+    /// Whether the enemy can walk on to the provided side: there is ground right in front of it
+    /// and no wall.
+    fn can_walk(&self, physics: &mut Physics, asset_manager: &AssetManager, left: bool) -> bool {
+        let position = self.position(physics);
+        let (width, height) = asset_manager.image_size(&self.kind.sprite);
+
+        let side = if left { -1.0 } else { 1.0 };
+        let reach = width as f32 / 2.0 + Self::LOOK_AHEAD;
+
+        let wall = Self::distance_to_ground(physics, position, na::Vector2::new(side, 0.0));
+        let ground = Self::distance_to_ground(
+            physics,
+            na::Point2::new(position.x + side * reach, position.y),
+            na::Vector2::new(0.0, 1.0),
+        );
+
+        wall.is_none_or(|distance| distance > reach)
+            && ground.is_some_and(|distance| distance <= height as f32 / 2.0 + Self::STEP_DOWN)
+    }
+
+    /// How far the nearest ground is from the provided point, in the provided direction.
+    fn distance_to_ground(
+        physics: &mut Physics,
+        origin: na::Point2<f32>,
+        direction: na::Vector2<f32>,
+    ) -> Option<f32> {
+        physics
+            .ray_cast(origin, direction, Layer::Ground)
+            .into_iter()
+            .map(|(_, _, hit)| hit.toi)
+            .min_by(f32::total_cmp)
+    }
+
+    /// Where the player is, if the enemy can see it: close enough and with no ground in between.
+    fn sees(&self, physics: &mut Physics, player: &mut Player) -> Option<na::Point2<f32>> {
+        let position = self.position(physics);
+        let player_position = player.position(physics);
+
+        let direction = player_position - position;
+
+        if direction.norm() > self.brain.behaviour().sight {
+            return None;
+        }
+
+        // Right on top of the player there is nothing in between, and no direction to cast to.
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Some(player_position),
+        };
+
+        let nearest = physics
+            .ray_cast(position, direction, Layer::Ground | Layer::Player)
+            .into_iter()
+            .min_by(|(_, _, a), (_, _, b)| a.toi.total_cmp(&b.toi));
+
+        match nearest {
+            Some((ObjectData::Player, _, _)) => Some(player_position),
+            _ => None,
+        }
+    }
+
     pub fn position(&self, physics: &mut Physics) -> na::Point2<f32> {
         let enemy_body = physics.get_rigid_body_mut(self.body);
         let enemy_position = isometry_to_point(enemy_body.position());
@@ -136,8 +489,8 @@ impl Enemy {
         enemy_position
     }
 
-    pub fn handle(&mut self) -> DefaultBodyHandle {
-        self.body
+    pub fn kind(&self) -> &EnemyKind {
+        &self.kind
     }
 
     pub fn destroy(&mut self, physics: &mut Physics) {
@@ -1,6 +1,8 @@
 pub mod barrel;
 pub mod bullet;
 pub mod cloud;
+pub mod effects;
 pub mod enemy;
+pub mod platform;
 pub mod player;
 pub mod tile;
@@ -0,0 +1,153 @@
+use ggez::{
+    graphics::{self, Color},
+    nalgebra::Point2,
+    Context, GameResult,
+};
+use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
+use graphics::DrawParam;
+use rapier2d::{dynamics::RigidBodyHandle, na};
+
+use crate::{
+    game::physics::{isometry_to_point, BodySpec, ObjectData, Physics, Shape},
+    utils::AssetManager,
+};
+
+pub use call_of_ferris::level::PlatformMotion;
+
+use super::player::Player;
+
+/// A piece of ground that moves, either along a path or down once the player stands on it.
+pub struct Platform {
+    body: RigidBodyHandle,
+    motion: PlatformMotion,
+
+    /// Where the platform was placed, the first point of its path.
+    origin: na::Point2<f32>,
+    /// How long the platform has been going along its path, in seconds.
+    time: f32,
+
+    /// Seconds left before a falling platform falls, counting down since the player stood on it.
+    fall_delay: Option<f32>,
+    /// How fast a falling platform is falling.
+    fall_speed: f32,
+}
+
+impl Platform {
+    /// How much grip a platform has, the same as the ground tiles.
+    const FRICTION: f32 = 0.4;
+    /// How long a falling platform holds the player before it falls, in seconds.
+    const FALL_DELAY: f32 = 0.5;
+    /// How far a falling platform falls before it is gone.
+    const FALL_DEPTH: f32 = 1000.0;
+
+    /// The image of a platform and the color it is tinted with.
+    pub fn look(motion: &PlatformMotion) -> (&'static str, Color) {
+        match motion {
+            PlatformMotion::Falling => ("ground_centre.png", Color::from_rgb(190, 150, 120)),
+            _ => ("ground_centre.png", Color::from_rgb(255, 210, 130)),
+        }
+    }
+
+    pub fn new(
+        pos_x: f32,
+        pos_y: f32,
+        physics: &mut Physics,
+        asset_manager: &AssetManager,
+        motion: PlatformMotion,
+    ) -> Self {
+        let size = asset_manager.image_size(Self::look(&motion).0);
+        let origin = na::Point2::new(pos_x, pos_y);
+
+        let body = physics.create_body(
+            BodySpec::kinematic(ObjectData::Ground, Shape::sprite(size))
+                .position(origin)
+                .friction(Self::FRICTION),
+        );
+
+        Self {
+            body,
+            motion,
+
+            origin,
+            time: 0.0,
+
+            fall_delay: None,
+            fall_speed: 0.0,
+        }
+    }
+
+    /// Move the platform for the next step of the physics world. Returns how far it moves, for
+    /// the player standing on it to move along.
+    pub fn update(&mut self, physics: &mut Physics, player: &Player) -> na::Vector2<f32> {
+        let position = self.position(physics);
+
+        let next = match self.motion {
+            PlatformMotion::Falling => {
+                if self.fall_delay.is_none() && player.standing_on() == Some(self.body) {
+                    self.fall_delay = Some(Self::FALL_DELAY);
+                }
+
+                match self.fall_delay {
+                    Some(delay) if delay > 0.0 => {
+                        self.fall_delay = Some(delay - Physics::STEP);
+                        position
+                    }
+                    Some(_) => {
+                        self.fall_speed += physics.gravity() * Physics::STEP;
+                        position + na::Vector2::new(0.0, self.fall_speed * Physics::STEP)
+                    }
+                    None => position,
+                }
+            }
+            _ => {
+                self.time += Physics::STEP;
+                self.origin + self.motion.offset_at(self.time)
+            }
+        };
+
+        physics.move_kinematic(self.body, next);
+
+        next - position
+    }
+
+    /// Whether a falling platform fell far enough to be removed.
+    pub fn is_gone(&self, physics: &mut Physics) -> bool {
+        self.position(physics).y > self.origin.y + Self::FALL_DEPTH
+    }
+
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        camera: &Camera,
+        physics: &mut Physics,
+        asset_manager: &AssetManager,
+    ) -> GameResult<()> {
+        let (image, tint) = Self::look(&self.motion);
+        let image = asset_manager.get_image(image);
+
+        let platform_position = physics.interpolated_position(self.body);
+        let platform_position =
+            camera.calculate_dest_point(Vec2::new(platform_position.x, platform_position.y));
+
+        graphics::draw(
+            ctx,
+            &image,
+            DrawParam::default()
+                .dest(Point2::new(platform_position.x, platform_position.y))
+                .offset(Point2::new(0.5, 0.5))
+                .color(tint),
+        )
+    }
+
+    pub fn destroy(&self, physics: &mut Physics) {
+        physics.destroy_body(self.body);
+    }
+
+    pub fn position(&self, physics: &mut Physics) -> na::Point2<f32> {
+        isometry_to_point(physics.get_rigid_body(self.body).position())
+    }
+
+    pub fn handle(&self) -> RigidBodyHandle {
+        self.body
+    }
+}
@@ -1,16 +1,68 @@
-use ggez::{event::KeyCode, graphics, input::keyboard, nalgebra::Point2, Context, GameResult};
+use ggez::{
+    event::KeyCode,
+    graphics::{self, Color},
+    input::keyboard,
+    nalgebra::Point2,
+    Context, GameResult,
+};
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 use graphics::DrawParam;
-use nphysics2d::object::DefaultBodyHandle;
-use nphysics2d::{algebra::Velocity2, nalgebra as na};
+use rapier2d::{dynamics::RigidBodyHandle, na};
+
+use call_of_ferris::level;
 
 use crate::{
-    game::physics::{isometry_to_point, point_to_isometry, Physics},
+    game::physics::{
+        isometry_to_point, BodySpec, Contact, ContactKind, Layer, LayerMask, ObjectData, Physics,
+        Shape,
+    },
     utils::AssetManager,
 };
 
 use super::bullet::{Grappling, PlayerWeapon, Turbofish, WeaponType};
 
+/// What hurts the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageSource {
+    /// Landing on a spike tile.
+    Spikes,
+    /// Getting shot by an enemy, with the damage of its weapon.
+    EnemyBullet(i32),
+    /// Running into an enemy.
+    Enemy,
+}
+
+impl DamageSource {
+    /// What hurts the player when it touches something with the provided user data.
+    pub fn of(data: ObjectData) -> Option<Self> {
+        match data {
+            ObjectData::Spikes => Some(DamageSource::Spikes),
+            ObjectData::EnemyBullet(damage) => Some(DamageSource::EnemyBullet(damage as i32)),
+            ObjectData::Enemy => Some(DamageSource::Enemy),
+            _ => None,
+        }
+    }
+
+    /// The health the player loses.
+    pub fn damage(self) -> i32 {
+        match self {
+            DamageSource::Spikes => 20,
+            DamageSource::EnemyBullet(damage) => damage,
+            DamageSource::Enemy => 15,
+        }
+    }
+
+    /// How fast the player gets thrown away from what hurt it, sideways and up.
+    fn knockback(self) -> na::Vector2<f32> {
+        match self {
+            // Back up, off of the spikes.
+            DamageSource::Spikes => na::Vector2::new(0.0, Player::JUMP_SPEED),
+            DamageSource::EnemyBullet(_) => na::Vector2::new(150.0, 100.0),
+            DamageSource::Enemy => na::Vector2::new(250.0, 150.0),
+        }
+    }
+}
+
 pub enum Direction {
     Left,
     Right,
@@ -21,40 +73,97 @@ pub struct Player {
     pub ammo: f32,
     pub health: i32,
 
+    /// The ammo and the health the player started the level with.
+    pub max_ammo: f32,
+    pub max_health: i32,
+
+    /// Whether the player can jump once more in the air, set by the level.
+    pub double_jump: bool,
+
     direction: Direction,
 
-    body: DefaultBodyHandle,
+    /// Steps since the player last stood on the ground.
+    air_steps: u32,
+    /// Steps left for a jump pressed too early to still happen once the player lands.
+    jump_buffer: u32,
+    /// Whether the jump key was down on the last step, so holding it only jumps once.
+    jump_held: bool,
+    /// Jumps left before landing again.
+    air_jumps: u32,
+    /// The body the player stood on at the last step, if it was on the ground.
+    standing_on: Option<RigidBodyHandle>,
+
+    /// When the player was last hurt, in the time of the physics world.
+    hurt_at: Option<f32>,
+
+    /// Half the width and height of the body.
+    half_size: na::Vector2<f32>,
+
+    body: RigidBodyHandle,
     pub weapons: Vec<PlayerWeapon>,
 }
 
 impl Player {
-    const SHIFT_JUICE: f32 = 10.0;
-    const JUMP_JUICE: f32 = 20.0;
+    /// Horizontal velocity added every step the player moves.
+    pub const SHIFT_JUICE: f32 = level::SHIFT_JUICE;
+    /// The upward velocity a jump starts the player with.
+    pub const JUMP_SPEED: f32 = level::JUMP_SPEED;
+
+    /// For how many steps after walking off the ground the player can still jump, a tenth of a
+    /// second.
+    const COYOTE_STEPS: u32 = 6;
+    /// For how many steps a jump pressed just before landing is remembered, a tenth of a second.
+    const JUMP_BUFFER_STEPS: u32 = 6;
+    /// How far below the feet of the player the ground can be for the player to stand on it.
+    const FOOT_REACH: f32 = 2.0;
+    /// For how long after getting hurt nothing can hurt the player again, in seconds.
+    const INVULNERABILITY: f32 = 1.0;
+    /// For how long the player flashes red after getting hurt, in seconds.
+    const HIT_FLASH: f32 = 0.15;
+
+    /// The ammo the player starts with, unless the level says otherwise.
+    pub const AMMO: f32 = 10.0;
+    /// The health the player starts with, unless the level says otherwise.
+    pub const HEALTH: i32 = 100;
 
     pub fn new(
-        ctx: &mut Context,
         pos_x: f32,
+        pos_y: f32,
         physics: &mut Physics,
         asset_manager: &AssetManager,
     ) -> Self {
-        let (_, height) = graphics::drawable_size(ctx);
-
-        let ferris = asset_manager.get_image("Some(ferris).png");
+        let (width, height) = asset_manager.image_size("Some(ferris).png");
 
-        let body = physics.create_player(
-            na::Point2::new(pos_x, height / 2.0 - 155.),
-            ferris.width(),
-            ferris.height(),
+        let body = physics.create_body(
+            BodySpec::dynamic(ObjectData::Player, Shape::sprite((width, height)))
+                .position(na::Point2::new(pos_x, pos_y))
+                .mass(10.0)
+                .damping(Physics::PLAYER_DAMPING),
         );
 
         let weapons = vec![];
 
         Self {
-            ammo: 10.0,
-            health: 100,
+            ammo: Self::AMMO,
+            health: Self::HEALTH,
+
+            max_ammo: Self::AMMO,
+            max_health: Self::HEALTH,
+
+            double_jump: false,
 
             direction: Direction::None,
 
+            air_steps: 0,
+            jump_buffer: 0,
+            jump_held: false,
+            air_jumps: 0,
+            standing_on: None,
+
+            hurt_at: None,
+
+            half_size: na::Vector2::new(width as f32, height as f32) / 2.0,
+
             body,
             weapons,
         }
@@ -74,17 +183,32 @@ impl Player {
         let ferris = asset_manager.get_image("Some(ferris).png");
         let turbofish_sniper = asset_manager.get_image("Some(sniper).png");
 
-        let player_position = self.position(physics);
+        let player_position = physics.interpolated_position(self.body);
         let ferris_position =
             camera.calculate_dest_point(Vec2::new(player_position.x, player_position.y));
 
+        // Flash red right after getting hurt, then blink for as long as nothing can hurt it.
+        let since_hurt = self.hurt_at.map(|hurt_at| physics.time() - hurt_at);
+
+        let tint = match since_hurt {
+            Some(since_hurt) if since_hurt < Self::HIT_FLASH => Color::from_rgb(255, 90, 90),
+            Some(since_hurt)
+                if since_hurt < Self::INVULNERABILITY
+                    && ((since_hurt * 10.0) as u32).is_multiple_of(2) =>
+            {
+                Color::new(1.0, 1.0, 1.0, 0.4)
+            }
+            _ => graphics::WHITE,
+        };
+
         // Draw the player
         graphics::draw(
             ctx,
             &ferris,
             DrawParam::default()
                 .dest(Point2::new(ferris_position.x, ferris_position.y))
-                .offset(Point2::new(0.5, 0.5)),
+                .offset(Point2::new(0.5, 0.5))
+                .color(tint),
         )?;
 
         graphics::draw(
@@ -95,7 +219,8 @@ impl Player {
                     ferris_position.x + 30.0,
                     ferris_position.y + 15.0,
                 ))
-                .offset(Point2::new(0.5, 0.5)),
+                .offset(Point2::new(0.5, 0.5))
+                .color(tint),
         )?;
 
         // Draw the player weapon
@@ -114,16 +239,17 @@ impl Player {
     }
 
     pub fn init(&mut self, physics: &mut Physics) {
-        let player_body = physics.get_rigid_body_mut(self.body);
-        let player_position = isometry_to_point(player_body.position());
-
-        let updated_position =
-            point_to_isometry(na::Point2::new(player_position.x, player_position.y - 40.0));
+        let player_position = self.position(physics);
 
-        player_body.set_position(updated_position);
+        physics.teleport(
+            self.body,
+            na::Point2::new(player_position.x, player_position.y - 40.0),
+        );
     }
 
-    pub fn update(&mut self, ctx: &mut Context, physics: &mut Physics) {
+    /// Move the player around with the keys. Called before every step of the physics world, so
+    /// that the player moves the same however often the world steps.
+    pub fn control(&mut self, ctx: &mut Context, physics: &mut Physics) {
         if keyboard::is_key_pressed(ctx, KeyCode::Left) {
             self.shift(physics, Direction::Left);
             self.set_direction(Direction::Left);
@@ -132,41 +258,169 @@ impl Player {
             self.set_direction(Direction::Right);
         }
 
+        self.standing_on = self.ground(physics);
+
+        if self.standing_on.is_some() {
+            self.air_steps = 0;
+            self.air_jumps = if self.double_jump { 1 } else { 0 };
+        } else {
+            self.air_steps = self.air_steps.saturating_add(1);
+        }
+
         // We are not adding Space key pressed in an else if statement as we want to jump while we are also moving to a specific direction in the x axis.
-        if keyboard::is_key_pressed(ctx, KeyCode::Space) {
-            self.go_boom(physics);
-            self.set_direction(Direction::None);
+        let jump_pressed = keyboard::is_key_pressed(ctx, KeyCode::Space);
+
+        if jump_pressed && !self.jump_held {
+            self.jump_buffer = Self::JUMP_BUFFER_STEPS;
+        }
+
+        self.jump_held = jump_pressed;
+
+        if self.jump_buffer > 0 {
+            let can_jump = if self.air_steps <= Self::COYOTE_STEPS {
+                true
+            } else if self.air_jumps > 0 {
+                self.air_jumps -= 1;
+                true
+            } else {
+                false
+            };
+
+            if can_jump {
+                // In the air right away, so that the coyote time does not jump again.
+                self.air_steps = Self::COYOTE_STEPS + 1;
+                self.jump_buffer = 0;
+
+                self.go_boom(physics);
+                self.set_direction(Direction::None);
+            } else {
+                self.jump_buffer -= 1;
+            }
         }
 
         // Same as the previous if statement. We want to shoot while moving and jumping around :)
         if keyboard::is_key_pressed(ctx, KeyCode::S) {
             // TODO: Move the shoot logic from game struct to this if statement
         }
+    }
 
-        for i in 0..self.weapons.len() {
-            let weapon = &mut self.weapons[i];
-
-            match weapon {
-                PlayerWeapon::Turbofish(fish) => {
-                    if fish.update(physics) {
-                        fish.destroy(physics);
-                        self.weapons.remove(i);
+    pub fn update(&mut self, ctx: &mut Context, physics: &mut Physics, contacts: &[Contact]) {
+        self.touch(physics, contacts);
 
-                        break;
-                    }
+        // Every bullet gets to see the contacts, they are only queued once.
+        self.weapons.retain_mut(|weapon| match weapon {
+            PlayerWeapon::Turbofish(fish) => {
+                if fish.update(contacts) {
+                    fish.destroy(physics);
+                    return false;
                 }
-                PlayerWeapon::Grappling(grapple) => {
-                    if keyboard::is_key_pressed(ctx, KeyCode::S) {
-                        grapple.update(physics);
-                    } else {
-                        self.weapons.remove(i);
-                        break;
-                    }
+
+                true
+            }
+            PlayerWeapon::Grappling(grapple) => {
+                if keyboard::is_key_pressed(ctx, KeyCode::S) {
+                    grapple.update(physics);
+                    return true;
                 }
+
+                false
+            }
+        });
+    }
+
+    /// Get hurt by what the player started touching during the last steps, and by what it still
+    /// touches once it is no longer invulnerable.
+    pub fn touch(&mut self, physics: &mut Physics, contacts: &[Contact]) {
+        let mut touching = contacts
+            .iter()
+            .filter(|contact| contact.kind == ContactKind::Started)
+            .filter_map(|contact| contact.other(self.body))
+            .collect::<Vec<_>>();
+
+        // Only the contacts that start are queued, so a player that stood on spikes all along
+        // would not get hurt again otherwise.
+        if self.hurt_at.is_some() && !self.is_invulnerable(physics) {
+            touching.extend(
+                physics
+                    .collisions(self.body, LayerMask::ALL)
+                    .into_iter()
+                    .map(|((_, data), other)| (other, data)),
+            );
+        }
+
+        for (other, data) in touching {
+            if let Some(source) = DamageSource::of(data) {
+                let from = isometry_to_point(physics.get_rigid_body(other).position());
+
+                self.hurt(physics, source, from);
             }
         }
     }
 
+    /// Lose health to the provided source at the provided position and get thrown away from it,
+    /// unless the player was hurt too recently.
+    pub fn hurt(&mut self, physics: &mut Physics, source: DamageSource, from: na::Point2<f32>) {
+        if self.is_invulnerable(physics) {
+            return;
+        }
+
+        self.health = (self.health - source.damage()).max(0);
+        self.hurt_at = Some(physics.time());
+
+        let side = (self.position(physics).x - from.x).signum();
+        let knockback = source.knockback();
+
+        let player_body = physics.get_rigid_body_mut(self.body);
+        let player_velocity = *player_body.linvel();
+
+        player_body.set_linvel(
+            na::Vector2::new(player_velocity.x + side * knockback.x, -knockback.y),
+            true,
+        );
+    }
+
+    /// Whether the player was hurt too recently to be hurt again.
+    pub fn is_invulnerable(&self, physics: &Physics) -> bool {
+        self.hurt_at
+            .is_some_and(|hurt_at| physics.time() - hurt_at < Self::INVULNERABILITY)
+    }
+
+    /// Destroy the Turbofish bullet with the provided body, if it is one of the player's.
+    /// Returns whether it was.
+    pub fn destroy_turbofish(&mut self, physics: &mut Physics, body: RigidBodyHandle) -> bool {
+        let position = self.weapons.iter().position(|weapon| match weapon {
+            PlayerWeapon::Turbofish(fish) => fish.handle() == body,
+            PlayerWeapon::Grappling(_) => false,
+        });
+
+        if let Some(i) = position {
+            if let PlayerWeapon::Turbofish(fish) = &mut self.weapons[i] {
+                fish.destroy(physics);
+            }
+
+            self.weapons.remove(i);
+        }
+
+        position.is_some()
+    }
+
+    /// The damage the weapon of the player with the provided body does: a turbofish, which is
+    /// destroyed by hitting, or a barrel pulled by the grappling gun. `None` for other bodies.
+    pub fn hit_with(&mut self, physics: &mut Physics, body: RigidBodyHandle) -> Option<i32> {
+        let grappled = self.weapons.iter().any(|weapon| match weapon {
+            PlayerWeapon::Grappling(grappling) => grappling.grapple_to() == body,
+            PlayerWeapon::Turbofish(_) => false,
+        });
+
+        if grappled {
+            Some(Grappling::DAMAGE)
+        } else if self.destroy_turbofish(physics, body) {
+            Some(Turbofish::DAMAGE)
+        } else {
+            None
+        }
+    }
+
     pub fn shoot(
         &mut self,
         physics: &mut Physics,
@@ -207,47 +461,80 @@ impl Player {
         player_position
     }
 
+    /// Move the player to the provided position in the world.
+    pub fn set_position(&mut self, physics: &mut Physics, position: na::Point2<f32>) {
+        physics.teleport(self.body, position);
+    }
+
+    /// The body the player stood on at the last step. `None` while in the air.
+    pub fn standing_on(&self) -> Option<RigidBodyHandle> {
+        self.standing_on
+    }
+
+    /// Move the player along with the platform it stands on, which moved by the provided offset
+    /// during the last step.
+    pub fn ride(&mut self, physics: &mut Physics, offset: na::Vector2<f32>) {
+        physics.carry(self.body, na::Vector2::new(offset.x, 0.0));
+
+        // Go up and down as fast as the platform, so that it does not throw the player off when it
+        // turns around. Unless the player is jumping off of it.
+        let platform_speed = offset.y / Physics::STEP;
+        let player_body = physics.get_rigid_body_mut(self.body);
+        let player_velocity = *player_body.linvel();
+
+        if player_velocity.y > platform_speed - Self::JUMP_SPEED / 2.0 {
+            player_body.set_linvel(na::Vector2::new(player_velocity.x, platform_speed), true);
+        }
+    }
+
+    /// The body the player stands on, the ground or something lying on it like a barrel. `None`
+    /// if the player is in the air.
+    fn ground(&mut self, physics: &mut Physics) -> Option<RigidBodyHandle> {
+        let position = self.position(physics);
+
+        // Looking down from both sides of the player, so it still stands on the edge of a tile.
+        [-1.0, 1.0]
+            .iter()
+            .flat_map(|side| {
+                let origin = na::Point2::new(
+                    position.x + side * (self.half_size.x - Self::FOOT_REACH),
+                    position.y,
+                );
+
+                physics.ray_cast(origin, na::Vector2::new(0.0, 1.0), Layer::Ground)
+            })
+            .filter(|(_, _, hit)| hit.toi <= self.half_size.y + Self::FOOT_REACH)
+            .min_by(|(_, _, a), (_, _, b)| a.toi.total_cmp(&b.toi))
+            .map(|(_, body, _)| body)
+    }
+
     pub fn go_boom(&mut self, physics: &mut Physics) {
         let player_body = physics.get_rigid_body_mut(self.body);
-        let player_velocity = player_body.velocity();
-
-        let new_velocity = Velocity2::new(
-            na::Vector2::new(
-                player_velocity.linear.x,
-                player_velocity.linear.y - Self::JUMP_JUICE,
-            ),
-            player_velocity.angular,
-        );
+        let player_velocity = *player_body.linvel();
 
-        player_body.set_velocity(new_velocity);
+        // Every jump goes as high, even a double jump while falling.
+        player_body.set_linvel(na::Vector2::new(player_velocity.x, 0.0), true);
+
+        let mass = player_body.mass();
+        player_body.apply_impulse(na::Vector2::new(0.0, -Self::JUMP_SPEED * mass), true);
     }
 
     fn shift(&mut self, physics: &mut Physics, direction: Direction) {
         let player_body = physics.get_rigid_body_mut(self.body);
-        let player_velocity = player_body.velocity();
+        let player_velocity = *player_body.linvel();
 
         match direction {
             Direction::Left => {
-                let new_velocity = Velocity2::new(
-                    na::Vector2::new(
-                        player_velocity.linear.x - Self::SHIFT_JUICE,
-                        player_velocity.linear.y,
-                    ),
-                    player_velocity.angular,
-                );
+                let new_velocity =
+                    na::Vector2::new(player_velocity.x - Self::SHIFT_JUICE, player_velocity.y);
 
-                player_body.set_velocity(new_velocity);
+                player_body.set_linvel(new_velocity, true);
             }
             Direction::Right => {
-                let new_velocity = Velocity2::new(
-                    na::Vector2::new(
-                        player_velocity.linear.x + Self::SHIFT_JUICE,
-                        player_velocity.linear.y,
-                    ),
-                    player_velocity.angular,
-                );
+                let new_velocity =
+                    na::Vector2::new(player_velocity.x + Self::SHIFT_JUICE, player_velocity.y);
 
-                player_body.set_velocity(new_velocity);
+                player_body.set_linvel(new_velocity, true);
             }
             Direction::None => {
                 panic!("Direction::None direction was passed in the Player::move_x() function where None value of the Direction enum was not expected. Panic!");
@@ -255,7 +542,7 @@ impl Player {
         }
     }
 
-    pub fn handle(&self) -> DefaultBodyHandle {
+    pub fn handle(&self) -> RigidBodyHandle {
         self.body
     }
 }
@@ -1,73 +1,71 @@
-use ggez::{graphics, nalgebra::Point2, Context, GameResult};
+use ggez::{
+    graphics::{self, Color},
+    nalgebra::Point2,
+    Context, GameResult,
+};
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 use graphics::DrawParam;
-use nphysics2d::nalgebra as na;
-use nphysics2d::object::DefaultBodyHandle;
+use rapier2d::{dynamics::RigidBodyHandle, na};
 
 use crate::{
-    game::physics::{isometry_to_point, Physics},
+    game::physics::{isometry_to_point, BodySpec, ObjectData, Physics, Shape},
     utils::AssetManager,
 };
 
-pub enum TileType {
-    Left,
-    Center,
-    Right,
-}
+pub use call_of_ferris::level::TileType;
 
 pub struct Tile {
-    width: f32,
-    height: f32,
-
-    body: DefaultBodyHandle,
+    body: RigidBodyHandle,
     tile_type: TileType,
 }
 
 impl Tile {
+    /// How much grip the ground has. The player has none of its own, so it gets half of this.
+    const FRICTION: f32 = 0.4;
+    /// How much of its speed something landing on a bouncy tile keeps going back up.
+    const BOUNCE: f32 = 0.9;
+
+    /// The image of a tile and the color it is tinted with.
+    pub fn look(tile_type: TileType) -> (&'static str, Color) {
+        match tile_type {
+            TileType::Left => ("ground_left.png", graphics::WHITE),
+            TileType::Center => ("ground_centre.png", graphics::WHITE),
+            TileType::Right => ("ground_right.png", graphics::WHITE),
+            TileType::OneWay => ("ground_centre.png", Color::new(1.0, 1.0, 1.0, 0.5)),
+            TileType::Ice => ("ground_centre.png", Color::from_rgb(150, 220, 255)),
+            TileType::Bouncy => ("ground_centre.png", Color::from_rgb(130, 255, 130)),
+            TileType::Spikes => ("ground_centre.png", Color::from_rgb(255, 110, 110)),
+        }
+    }
+
     pub fn new(
-        ctx: &mut Context,
         pos_x: f32,
+        pos_y: f32,
         physics: &mut Physics,
         asset_manager: &AssetManager,
         tile_type: TileType,
     ) -> Self {
-        let (_, height) = graphics::drawable_size(ctx);
-
-        let tile_width;
-        let tile_height;
+        let size = asset_manager.image_size(Self::look(tile_type).0);
 
-        let pos_y = height / 2.0 - 64.0;
+        let data = match tile_type {
+            TileType::Spikes => ObjectData::Spikes,
+            _ => ObjectData::Ground,
+        };
 
-        match tile_type {
-            TileType::Left => {
-                let ground_left = asset_manager.get_image("ground_left.png");
+        let spec = BodySpec::fixed(data, Shape::sprite(size))
+            .position(na::Point2::new(pos_x, pos_y))
+            .friction(Self::FRICTION);
 
-                tile_width = ground_left.width();
-                tile_height = ground_left.height();
-            }
-            TileType::Center => {
-                let ground_centre = asset_manager.get_image("ground_centre.png");
+        let spec = match tile_type {
+            TileType::OneWay => spec.one_way(true),
+            TileType::Ice => spec.friction(0.0),
+            TileType::Bouncy => spec.restitution(Self::BOUNCE),
+            _ => spec,
+        };
 
-                tile_width = ground_centre.width();
-                tile_height = ground_centre.height();
-            }
-            TileType::Right => {
-                let ground_right = asset_manager.get_image("ground_right.png");
+        let body = physics.create_body(spec);
 
-                tile_width = ground_right.width();
-                tile_height = ground_right.height();
-            }
-        }
-
-        let body = physics.create_tile(na::Point2::new(pos_x, pos_y), tile_width, tile_height);
-
-        Self {
-            tile_type,
-            body,
-
-            width: tile_width as f32,
-            height: tile_height as f32,
-        }
+        Self { tile_type, body }
     }
 
     pub fn draw(
@@ -77,43 +75,46 @@ impl Tile {
         physics: &mut Physics,
         asset_manager: &AssetManager,
     ) -> GameResult<()> {
-        let ground_left = asset_manager.get_image("ground_left.png");
-        let ground_centre = asset_manager.get_image("ground_centre.png");
-        let ground_right = asset_manager.get_image("ground_right.png");
+        let (image, tint) = Self::look(self.tile_type);
+        let image = asset_manager.get_image(image);
 
         let ground_position = self.position(physics);
         let tile_position =
             camera.calculate_dest_point(Vec2::new(ground_position.x, ground_position.y));
 
-        match self.tile_type {
-            TileType::Left => {
-                graphics::draw(
-                    ctx,
-                    &ground_left,
-                    DrawParam::default()
-                        .dest(Point2::new(tile_position.x, tile_position.y))
-                        .offset(Point2::new(0.5, 0.5)),
+        graphics::draw(
+            ctx,
+            &image,
+            DrawParam::default()
+                .dest(Point2::new(tile_position.x, tile_position.y))
+                .offset(Point2::new(0.5, 0.5))
+                .color(tint),
+        )?;
+
+        if self.tile_type == TileType::Spikes {
+            let half_width = image.width() as f32 / 2.0;
+            let top = tile_position.y - image.height() as f32 / 2.0;
+
+            // A row of four spikes along the top of the tile.
+            let mut spikes = graphics::MeshBuilder::new();
+            let spike_width = half_width / 2.0;
+
+            for spike in 0..4 {
+                let left = tile_position.x - half_width + spike as f32 * spike_width;
+
+                spikes.polygon(
+                    graphics::DrawMode::fill(),
+                    &[
+                        Point2::new(left, top),
+                        Point2::new(left + spike_width / 2.0, top - 12.0),
+                        Point2::new(left + spike_width, top),
+                    ],
+                    Color::from_rgb(200, 200, 210),
                 )?;
             }
 
-            TileType::Center => {
-                graphics::draw(
-                    ctx,
-                    &ground_centre,
-                    DrawParam::default()
-                        .dest(Point2::new(tile_position.x, tile_position.y))
-                        .offset(Point2::new(0.5, 0.5)),
-                )?;
-            }
-            TileType::Right => {
-                graphics::draw(
-                    ctx,
-                    &ground_right,
-                    DrawParam::default()
-                        .dest(Point2::new(tile_position.x, tile_position.y))
-                        .offset(Point2::new(0.5, 0.5)),
-                )?;
-            }
+            let spikes = spikes.build(ctx)?;
+            graphics::draw(ctx, &spikes, DrawParam::default())?;
         }
 
         Ok(())
@@ -125,8 +126,4 @@ impl Tile {
 
         ground_position
     }
-
-    pub fn dimensions(&self) -> na::Point2<f32> {
-        na::Point2::new(self.width, self.height)
-    }
 }
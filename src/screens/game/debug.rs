@@ -0,0 +1,138 @@
+//! The physics debug overlay, drawn on top of the game. Each of its views is switched on and off
+//! while playing with its own function key.
+
+use ggez::{
+    event::KeyCode,
+    graphics::{self, Color, DrawParam, Font, Scale, Text, TextFragment},
+    nalgebra::Point2,
+    Context, GameResult,
+};
+use ggez_goodies::camera::Camera;
+
+use super::physics::Physics;
+
+/// What the overlay can show about the physics world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    /// The outline of every collider.
+    Shapes,
+    /// The points where colliders touch and the normals pushing them apart.
+    Contacts,
+    /// The rays cast during the frame, like the ones looking for the ground under the player.
+    RayCasts,
+    /// Where the moving bodies are going, and how fast.
+    Velocities,
+    /// Which bodies are asleep, and so not simulated until something wakes them up.
+    Sleeping,
+    /// The axis aligned bounding boxes of the colliders.
+    Bounds,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 6] = [
+        DebugView::Shapes,
+        DebugView::Contacts,
+        DebugView::RayCasts,
+        DebugView::Velocities,
+        DebugView::Sleeping,
+        DebugView::Bounds,
+    ];
+
+    /// The key that switches the view on and off.
+    pub fn key(self) -> KeyCode {
+        match self {
+            DebugView::Shapes => KeyCode::F1,
+            DebugView::Contacts => KeyCode::F2,
+            DebugView::RayCasts => KeyCode::F3,
+            DebugView::Velocities => KeyCode::F4,
+            DebugView::Sleeping => KeyCode::F5,
+            DebugView::Bounds => KeyCode::F6,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Shapes => "shapes",
+            DebugView::Contacts => "contacts",
+            DebugView::RayCasts => "ray casts",
+            DebugView::Velocities => "velocities",
+            DebugView::Sleeping => "sleeping",
+            DebugView::Bounds => "bounds",
+        }
+    }
+}
+
+/// The views of the overlay that are switched on, none to begin with.
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    shown: Vec<DebugView>,
+}
+
+impl DebugOverlay {
+    /// Switch the view of the key on or off. Returns whether the key belongs to a view.
+    pub fn key_press(&mut self, keycode: KeyCode) -> bool {
+        let view = DebugView::ALL
+            .iter()
+            .copied()
+            .find(|view| view.key() == keycode);
+
+        if let Some(view) = view {
+            if self.shows(view) {
+                self.shown.retain(|&shown| shown != view);
+            } else {
+                self.shown.push(view);
+            }
+        }
+
+        view.is_some()
+    }
+
+    pub fn shows(&self, view: DebugView) -> bool {
+        self.shown.contains(&view)
+    }
+
+    /// Whether any of the views is switched on.
+    pub fn is_shown(&self) -> bool {
+        !self.shown.is_empty()
+    }
+
+    /// Draw the views that are switched on, and the keys of all of them at the bottom of the
+    /// screen.
+    pub fn draw(
+        &self,
+        ctx: &mut Context,
+        camera: &Camera,
+        physics: &Physics,
+        font: Font,
+    ) -> GameResult<()> {
+        if !self.is_shown() {
+            return Ok(());
+        }
+
+        physics.draw_debug(ctx, camera, self)?;
+
+        let (_, height) = graphics::drawable_size(ctx);
+        let mut legend = Text::default();
+
+        for view in DebugView::ALL.iter().copied() {
+            let color = if self.shows(view) {
+                Color::from_rgb(214, 208, 132)
+            } else {
+                Color::from_rgb(120, 120, 120)
+            };
+
+            legend.add(
+                TextFragment::new(format!("{:?} {}   ", view.key(), view.name()))
+                    .font(font)
+                    .scale(Scale::uniform(15.0))
+                    .color(color),
+            );
+        }
+
+        graphics::draw(
+            ctx,
+            &legend,
+            DrawParam::default().dest(Point2::new(20.0, height - 30.0)),
+        )
+    }
+}
@@ -13,16 +13,20 @@ use graphics::{GlBackendSpec, Scale, ShaderGeneric, TextFragment};
 use mint::Vector2;
 use rand::Rng;
 
+use call_of_ferris::level::Level;
+
 use crate::{
     game::components::{
         bullet::{PlayerWeapon, WeaponType},
         cloud::Cloud,
+        effects::Effects,
         player::Direction,
     },
-    game::map::Map,
+    game::debug::{DebugOverlay, DebugView},
+    game::map::{load_level, Map},
     game::physics::Physics,
     play,
-    utils::{lerp, remap, AssetManager, ParticleSystem},
+    utils::{lerp, remap, AssetManager},
     Screen,
 };
 
@@ -41,6 +45,8 @@ pub struct Game {
     physics: Physics,
     /// Camera to see the world.
     camera: Camera,
+    /// What is drawn on top of the world to see what the physics are up to.
+    debug: DebugOverlay,
 
     // TODO: Refactor the rest of the fields
     clouds: Vec<Cloud>,
@@ -50,7 +56,7 @@ pub struct Game {
 
     elapsed_shake: Option<(f32, Vec2, f32)>,
     tics: Option<i32>,
-    particles: Vec<ParticleSystem>,
+    effects: Effects,
     ui_lerp: HashMap<String, f32>,
 
     dim_shader: ShaderGeneric<GlBackendSpec, Dim>,
@@ -58,18 +64,40 @@ pub struct Game {
 
     draw_end_text: (bool, Option<usize>, bool, bool), // Thread Sleeped?, Current Iters, Done?, Win?
     can_die: bool,
+
+    /// Is there a level after this one in the campaign?
+    has_next_level: bool,
 }
 
 impl Game {
-    pub fn create(ctx: &mut Context, asset_manager: Rc<AssetManager>) -> Mutex<Self> {
+    pub fn create(
+        ctx: &mut Context,
+        asset_manager: Rc<AssetManager>,
+        map_id: &str,
+        has_next_level: bool,
+    ) -> GameResult<Mutex<Self>> {
+        let level = load_level(map_id, &asset_manager)?;
+
+        Self::from_level(ctx, asset_manager, &level, has_next_level)
+    }
+
+    /// Create the game screen for an already parsed level, like the one being play tested in the editor.
+    pub fn from_level(
+        ctx: &mut Context,
+        asset_manager: Rc<AssetManager>,
+        level: &Level,
+        has_next_level: bool,
+    ) -> GameResult<Mutex<Self>> {
         let (width, height) = graphics::drawable_size(ctx);
 
         let mut camera = Camera::new(width as u32, height as u32, width, height);
 
         let mut rng = rand::thread_rng();
 
-        let mut physics = Physics::new();
-        let mut map = Map::parse(ctx, "01", &mut physics, &asset_manager);
+        Map::check_assets(level, &asset_manager)?;
+
+        let mut physics = Map::physics(level);
+        let mut map = Map::build(level, &mut physics, &asset_manager);
 
         let mut clouds = vec![];
 
@@ -82,12 +110,11 @@ impl Game {
             dim_constant,
             "Dim",
             None,
-        )
-        .unwrap();
+        )?;
 
         let mut ui_lerp = HashMap::new();
 
-        ui_lerp.insert(String::from("ammo"), map.player.ammo as f32);
+        ui_lerp.insert(String::from("ammo"), map.player.ammo);
         ui_lerp.insert(String::from("health"), map.player.health as f32);
         ui_lerp.insert(String::from("using"), map.using.as_ref().unwrap().1);
 
@@ -107,7 +134,7 @@ impl Game {
             ));
         }
 
-        Mutex::new(Self {
+        Ok(Mutex::new(Self {
             map,
             physics,
 
@@ -116,17 +143,20 @@ impl Game {
             asset_manager,
 
             camera,
+            debug: DebugOverlay::default(),
 
             elapsed_shake: None,
             tics: None,
-            particles: vec![],
+            effects: Effects::default(),
             ui_lerp,
 
             dim_shader,
             dim_constant,
             draw_end_text: (false, None, false, false),
             can_die: true,
-        })
+
+            has_next_level,
+        }))
     }
 
     pub fn draw(&mut self, ctx: &mut Context) -> GameResult<Option<Screen>> {
@@ -166,8 +196,8 @@ impl Game {
                 for line in self
                     .map
                     .end
-                    .as_ref()
-                    .unwrap()
+                    .as_deref()
+                    .unwrap_or_default()
                     .split("\\n")
                     .collect::<Vec<_>>()
                 {
@@ -187,7 +217,7 @@ impl Game {
                     draw_pos += 20.0;
                 }
 
-                // Press & to go to menu screen
+                // Press & to go to the next level or the menu screen
                 let menu_rect = graphics::Mesh::new_rectangle(
                     ctx,
                     graphics::DrawMode::fill(),
@@ -206,9 +236,13 @@ impl Game {
                     &Text::new(TextFragment::new("Press & go to the").font(consolas));
 
                 let menu_screen = &Text::new(
-                    TextFragment::new("MENU SCREEN")
-                        .font(consolas)
-                        .scale(Scale::uniform(20.0)),
+                    TextFragment::new(if self.has_next_level {
+                        "NEXT LEVEL"
+                    } else {
+                        "MENU SCREEN"
+                    })
+                    .font(consolas)
+                    .scale(Scale::uniform(20.0)),
                 );
 
                 graphics::draw(ctx, &menu_rect, DrawParam::default())?;
@@ -282,6 +316,21 @@ impl Game {
     fn inner_draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx, graphics::BLACK);
 
+        if let Some(background) = &self.map.background {
+            let (width, height) = graphics::drawable_size(ctx);
+            let background = self.asset_manager.get_image(background);
+
+            // Cover the whole screen while keeping the proportions of the image.
+            let scale =
+                (width / background.width() as f32).max(height / background.height() as f32);
+
+            graphics::draw(
+                ctx,
+                &background,
+                DrawParam::default().scale(Vector2 { x: scale, y: scale }),
+            )?;
+        }
+
         // Clouds
         for cloud in &mut self.clouds {
             cloud.draw(ctx, &self.asset_manager)?;
@@ -292,6 +341,11 @@ impl Game {
             tile.draw(ctx, &self.camera, &mut self.physics, &self.asset_manager)?;
         }
 
+        // Platforms
+        for platform in &mut self.map.platforms {
+            platform.draw(ctx, &self.camera, &mut self.physics, &self.asset_manager)?;
+        }
+
         // Enemies
         for enemy in &mut self.map.enemies {
             enemy.draw(ctx, &self.camera, &mut self.physics, &self.asset_manager)?;
@@ -302,21 +356,26 @@ impl Game {
             boom.draw(ctx, &self.camera, &mut self.physics, &self.asset_manager)?;
         }
 
+        // Enemy bullets
+        for bullet in &self.map.enemy_bullets {
+            bullet.draw(ctx, &self.camera, &mut self.physics)?;
+        }
+
         // Player
         self.map
             .player
             .draw(ctx, &self.camera, &mut self.physics, &self.asset_manager)?;
 
-        // Particles
-        for sys in &mut self.particles {
-            sys.draw(ctx, &mut self.physics, &mut self.camera)?;
-        }
+        // Particles, damage numbers and shockwaves
+        let consolas = self.asset_manager.get_font("Consolas.ttf");
+        self.effects
+            .draw(ctx, &mut self.camera, &mut self.physics, consolas)?;
 
         // User Profile, etc..
         self.draw_ui(ctx)?;
 
-        #[cfg(feature = "debug")]
-        self.physics.draw_colliders(ctx, &self.camera)?;
+        self.debug
+            .draw(ctx, &self.camera, &self.physics, consolas)?;
 
         Ok(())
     }
@@ -367,19 +426,50 @@ impl Game {
             graphics::Rect::new(
                 ((profile.width() / 2) + 10) as f32,
                 (profile.height() / 3) as f32,
-                remap(self.map.player.ammo as f32, 0., 10., 0., 150.),
+                remap(
+                    self.map.player.ammo,
+                    0.,
+                    self.map.player.max_ammo.max(1.0),
+                    0.,
+                    150.,
+                ),
                 15.,
             ),
             Color::from_rgb(21, 156, 228),
         )?;
 
+        // The health the player just lost, shrinking behind the health it has left.
+        let lost_hp_rect = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                ((profile.width() / 2) + 10) as f32,
+                (profile.height() / 5) as f32,
+                remap(
+                    self.ui_lerp["health"].max(0.),
+                    0.,
+                    self.map.player.max_health as f32,
+                    0.,
+                    150.,
+                ),
+                15.,
+            ),
+            Color::from_rgb(230, 90, 80),
+        )?;
+
         let cur_hp_rect = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
             graphics::Rect::new(
                 ((profile.width() / 2) + 10) as f32,
                 (profile.height() / 5) as f32,
-                remap(self.map.player.health as f32, 0., 100., 0., 150.),
+                remap(
+                    self.map.player.health as f32,
+                    0.,
+                    self.map.player.max_health as f32,
+                    0.,
+                    150.,
+                ),
                 15.,
             ),
             Color::from_rgb(34, 205, 124),
@@ -391,6 +481,8 @@ impl Game {
 
         graphics::draw(ctx, &cur_ammo_rect, DrawParam::default())?;
 
+        graphics::draw(ctx, &lost_hp_rect, DrawParam::default())?;
+
         graphics::draw(ctx, &cur_hp_rect, DrawParam::default())?;
 
         graphics::draw(
@@ -422,6 +514,20 @@ impl Game {
             DrawParam::default().dest(Point2::new((width - evildoers_dim.0 as f32) - 40., 20.)),
         )?;
 
+        let score = &Text::new(
+            TextFragment::new(format!("Score {}", self.map.score))
+                .font(consolas)
+                .scale(Scale::uniform(20.)),
+        );
+
+        let score_dim = score.dimensions(ctx);
+
+        graphics::draw(
+            ctx,
+            score,
+            DrawParam::default().dest(Point2::new((width - score_dim.0 as f32) - 40., 50.)),
+        )?;
+
         let info = &Text::new(
             TextFragment::new(format!("Using {}", self.map.using.as_ref().unwrap().0))
                 .font(consolas)
@@ -440,34 +546,80 @@ impl Game {
     }
 
     pub fn update(&mut self, ctx: &mut Context) -> GameResult<Option<crate::Screen>> {
+        if let Some(music) = &self.map.music {
+            let mut music = self.asset_manager.get_sound(music).lock().unwrap();
+
+            if !music.playing() {
+                music.set_repeat(true);
+                music.play()?;
+            }
+        }
+
         if let Some(t) = self.tics {
             if self.tics.is_some() && self.dim_constant.rate != 0.5 {
                 self.dim_constant.rate = lerp(self.dim_constant.rate, 0.5, 0.1);
                 self.dim_shader.send(ctx, self.dim_constant)?;
             }
 
-            if timer::ticks(ctx) % t as usize == 0 {
-                return self.inner_update(ctx);
-            }
+            // The world goes `t` times slower than real time.
+            self.physics.set_time_scale(1.0 / t as f32);
         } else {
-            return self.inner_update(ctx);
+            self.physics.set_time_scale(1.0);
         }
 
-        Ok(None)
+        self.inner_update(ctx)
     }
 
     fn inner_update(&mut self, ctx: &mut Context) -> GameResult<Option<crate::Screen>> {
-        let (_, height) = graphics::drawable_size(ctx);
+        self.physics
+            .record_ray_casts(self.debug.shows(DebugView::RayCasts));
+
+        // Take as many fixed time steps in our physics world as the time that passed asks for!
+        // This runs once per update and not once per frame, so an update is what passed.
+        let steps = self.physics.steps(1.0 / crate::UPDATES_PER_SECOND as f32);
+
+        for _ in 0..steps {
+            self.map.player.control(ctx, &mut self.physics);
+
+            let (physics, player) = (&mut self.physics, &self.map.player);
+            let moves = self
+                .map
+                .platforms
+                .iter_mut()
+                .map(|platform| platform.update(physics, player))
+                .collect::<Vec<_>>();
+
+            self.physics.step();
+
+            // The player rides the platform it stands on.
+            for (platform, offset) in self.map.platforms.iter().zip(moves) {
+                if self.map.player.standing_on() == Some(platform.handle()) {
+                    self.map.player.ride(&mut self.physics, offset);
+                }
+            }
+        }
+
+        // The falling platforms that fell out of the world.
+        let physics = &mut self.physics;
+        self.map.platforms.retain(|platform| {
+            let gone = platform.is_gone(physics);
 
-        // Take a time step in our physics world!
-        self.physics.step();
+            if gone {
+                platform.destroy(physics);
+            }
+
+            !gone
+        });
+
+        // What started and stopped touching during the steps, for everything to react to.
+        let contacts = self.physics.drain_contacts();
 
         // Update our player
-        self.map.player.update(ctx, &mut self.physics);
-        self.camera.move_to(Vec2::new(
-            self.map.player.position(&mut self.physics).x,
-            self.map.player.position(&mut self.physics).y,
-        ));
+        self.map.player.update(ctx, &mut self.physics, &contacts);
+
+        let player_position = self.physics.interpolated_position(self.map.player.handle());
+        self.camera
+            .move_to(Vec2::new(player_position.x, player_position.y));
 
         // Update our lovely clouds
         for cloud in &mut self.clouds {
@@ -478,61 +630,83 @@ impl Game {
             self.draw_end_text.3 = true;
             self.can_die = false;
 
-            if self.draw_end_text.1.is_none() {
-                self.draw_end_text.1 = Some(timer::ticks(ctx));
-            } else if !self.draw_end_text.2 {
-                if timer::ticks(ctx) - self.draw_end_text.1.unwrap() > 30 {
-                    self.draw_end_text.0 = true;
-                    self.draw_end_text.2 = true;
-                }
-            } else {
-                self.tics = Some(1);
+            if let Some(started) = self.draw_end_text.1 {
+                if !self.draw_end_text.2 {
+                    if timer::ticks(ctx) - started > 30 {
+                        self.draw_end_text.0 = true;
+                        self.draw_end_text.2 = true;
+                    }
+                } else {
+                    self.tics = Some(1);
 
-                if self.dim_constant.rate != 0.0 {
-                    self.dim_constant.rate = lerp(self.dim_constant.rate, 0.0, 0.1);
-                    self.dim_shader.send(ctx, self.dim_constant)?;
+                    if self.dim_constant.rate != 0.0 {
+                        self.dim_constant.rate = lerp(self.dim_constant.rate, 0.0, 0.1);
+                        self.dim_shader.send(ctx, self.dim_constant)?;
+                    }
                 }
+            } else {
+                self.draw_end_text.1 = Some(timer::ticks(ctx));
             }
         }
 
-        if self.map.player.position(&mut self.physics).y > height && self.can_die {
+        if self.map.player.position(&mut self.physics).y > self.map.death_y && self.can_die {
+            return Ok(Some(Screen::Dead));
+        }
+
+        if self.map.player.health <= 0 && self.can_die {
             return Ok(Some(Screen::Dead));
         }
 
-        for id in 0..self.map.enemies.len() {
-            let enemy = &mut self.map.enemies[id];
+        // Every enemy gets to see the contacts before the destroyed ones are removed, as they are
+        // only queued once.
+        let mut destroyed = vec![];
 
+        for (id, enemy) in self.map.enemies.iter_mut().enumerate() {
             if enemy.update(
                 &mut self.physics,
                 &self.asset_manager,
-                &mut self.particles,
+                &mut self.effects,
                 &mut self.map.player,
+                &mut self.map.enemy_bullets,
+                &contacts,
             ) {
-                self.map.enemies.remove(id);
-                let cam_loc = self.camera.location();
-                let org_pos = cam_loc.data.as_slice();
+                destroyed.push(id);
+            }
+        }
 
-                self.elapsed_shake = Some((0., Vec2::new(org_pos[0], org_pos[1]), 3.));
-                self.camera_shakeke();
+        if !destroyed.is_empty() {
+            for id in destroyed.into_iter().rev() {
+                let enemy = self.map.enemies.remove(id);
+                self.map.score += enemy.kind().score;
+            }
+
+            let cam_loc = self.camera.location();
+            let org_pos = cam_loc.data.as_slice();
 
-                break;
-            };
+            self.elapsed_shake = Some((0., Vec2::new(org_pos[0], org_pos[1]), 3.));
+            self.camera_shakeke();
         }
 
-        for id in 0..self.map.barrels.len() {
-            if self.map.barrels[id].update(
-                &mut self.physics,
-                &self.asset_manager,
-                &mut self.particles,
-                &mut self.map.player,
-            ) {
-                self.map.barrels.remove(id);
-                let cam_loc = self.camera.location();
-                let org_pos = cam_loc.data.as_slice();
+        // The enemy bullets that hit something or flew for too long are gone.
+        let physics = &mut self.physics;
+        self.map
+            .enemy_bullets
+            .retain_mut(|bullet| !bullet.update(physics, &contacts));
 
-                self.elapsed_shake = Some((0., Vec2::new(org_pos[0], org_pos[1]), 5.));
-                self.camera_shakeke();
-            }
+        let (physics, asset_manager) = (&mut self.physics, &self.asset_manager);
+        let (particles, player) = (&mut self.effects.particles, &mut self.map.player);
+        let barrels = self.map.barrels.len();
+
+        self.map.barrels.retain_mut(|barrel| {
+            !barrel.update(physics, asset_manager, particles, player, &contacts)
+        });
+
+        if self.map.barrels.len() < barrels {
+            let cam_loc = self.camera.location();
+            let org_pos = cam_loc.data.as_slice();
+
+            self.elapsed_shake = Some((0., Vec2::new(org_pos[0], org_pos[1]), 5.));
+            self.camera_shakeke();
         }
 
         if let Some(s) = self.elapsed_shake {
@@ -544,15 +718,7 @@ impl Game {
             }
         }
 
-        for id in 0..self.particles.len() {
-            let sys = &mut self.particles[id];
-
-            if sys.update(ctx, &mut self.physics) {
-                self.particles.remove(id);
-
-                break;
-            }
-        }
+        self.effects.update(ctx, &mut self.physics);
 
         for v in &mut self.ui_lerp {
             match v.0.as_str() {
@@ -564,8 +730,9 @@ impl Game {
                     }
                 }
 
+                // The health bar catches up with the health the player has left.
                 "health" => {
-                    // TODO: Health lerping
+                    *v.1 = lerp(*v.1, self.map.player.health as f32, 0.1);
                 }
 
                 "using" => {
@@ -581,6 +748,10 @@ impl Game {
     }
 
     pub fn key_press(&mut self, keycode: KeyCode) -> Option<crate::Screen> {
+        if self.debug.key_press(keycode) {
+            return None;
+        }
+
         match keycode {
             KeyCode::S => {
                 let ui_lerp = self.ui_lerp.clone();
@@ -605,6 +776,11 @@ impl Game {
                 self.tics = Some(6);
             }
             KeyCode::Key7 => {
+                // Returning the play screen while already playing means go to the next level.
+                if self.draw_end_text.2 && self.has_next_level {
+                    return Some(Screen::Play);
+                }
+
                 return Some(Screen::Menu);
             }
             KeyCode::Key8 => {
@@ -639,6 +815,51 @@ impl Game {
         self.map.player.set_direction(Direction::None);
     }
 
+    /// Rebuild the map and the physics world from a changed level, keeping the player where it was.
+    pub fn reload(&mut self, level: &Level) -> GameResult<()> {
+        Map::check_assets(level, &self.asset_manager)?;
+
+        let position = self.map.player.position(&mut self.physics);
+
+        let mut physics = Map::physics(level);
+        let mut map = Map::build(level, &mut physics, &self.asset_manager);
+
+        map.player.set_position(&mut physics, position);
+        map.player.ammo = self.map.player.ammo.min(map.player.max_ammo);
+        map.player.health = self.map.player.health.min(map.player.max_health);
+
+        if map.music != self.map.music {
+            self.stop_music();
+        }
+
+        // The effects live in the old physics world.
+        self.effects = Effects::default();
+
+        self.map = map;
+        self.physics = physics;
+
+        Ok(())
+    }
+
+    /// Stop the music of the level, it starts again on the next update.
+    pub fn stop_music(&self) {
+        if let Some(music) = &self.map.music {
+            let mut music = self.asset_manager.get_sound(music).lock().unwrap();
+
+            if music.playing() {
+                music.stop();
+            }
+        }
+    }
+
+    /// The window was resized. Keep the camera where it is but map the world to the new screen size.
+    pub fn resize(&mut self, width: f32, height: f32) {
+        let location = self.camera.location();
+
+        self.camera = Camera::new(width as u32, height as u32, width, height);
+        self.camera.move_to(location);
+    }
+
     /// Give the camera a shakey shakey.
     fn camera_shakeke(&mut self) {
         let mut rng = rand::thread_rng();
@@ -654,3 +875,9 @@ impl Game {
         self.elapsed_shake = Some((elapsed.0 + 0.1, elapsed.1, magnitude));
     }
 }
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.stop_music();
+    }
+}
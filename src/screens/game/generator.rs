@@ -0,0 +1,177 @@
+//! Seeded procedural levels for the endless mode.
+//!
+//! A level is generated as a [`MapSource`], so it goes through the same parser and layout as the
+//! hand made .map files and can be saved as one. The same seed always generates the same level.
+//!
+//! Every gap is checked against [`jump_reach`], like the hand made maps are by
+//! [`Level::lint`], so a generated level can always be finished. Gaps are also never wider
+//! than [`WIDEST_GAP`], to leave some room for a jump that does not go perfectly.
+
+use call_of_ferris::level::{
+    glyph_layout, jump_reach, Level, MapSource, Roster, TileType, GRAVITY, ROW_HEIGHT,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::components::player::Player;
+
+/// The widest gap of a generated level, about two thirds of the widest jump on flat ground.
+const WIDEST_GAP: f32 = 300.0;
+
+/// The ammo the player gets for each enemy of a generated level. A gopher takes two turbofish.
+const AMMO_PER_ENEMY: u32 = 3;
+
+/// How the levels get harder, see [`Difficulty::stage`].
+pub struct Difficulty {
+    /// Number of platforms in the level, the player starts on the first one.
+    pub platforms: usize,
+    /// The least and the most center tiles a platform has between its edges.
+    pub platform_length: (usize, usize),
+    /// How much of the player's jump reach the widest gaps take, from 0 to 1.
+    pub gap: f32,
+    /// The most rows the platforms can be above or below the first one.
+    pub max_height: i32,
+    /// Chance of a center tile having an enemy on it.
+    pub enemy_chance: f32,
+    /// Chance of a center tile having a barrel on it.
+    pub barrel_chance: f32,
+}
+
+impl Difficulty {
+    /// The difficulty of a stage of the endless mode, starting from 0. Every stage has more and
+    /// shorter platforms, wider gaps, bigger steps and more enemies than the one before.
+    pub fn stage(stage: u32) -> Self {
+        let stage = stage.min(20) as usize;
+
+        Self {
+            platforms: 5 + stage,
+            platform_length: (4usize.saturating_sub(stage / 3).max(1), 8 - stage / 4),
+            gap: (0.45 + 0.05 * stage as f32).min(1.0),
+            max_height: 1 + stage as i32 / 4,
+            enemy_chance: (0.15 + 0.02 * stage as f32).min(0.5),
+            barrel_chance: 0.1,
+        }
+    }
+}
+
+/// Width of the gap between a `]` and a `[` with the provided number of `_` between them.
+fn gap_width(spaces: usize) -> f32 {
+    let (right_offset, right_advance) = glyph_layout(']').unwrap();
+    let (left_offset, _) = glyph_layout('[').unwrap();
+    let (_, space_advance) = glyph_layout('_').unwrap();
+
+    right_advance + spaces as f32 * space_advance + left_offset
+        - TileType::Left.width() / 2.0
+        - right_offset
+        - TileType::Right.width() / 2.0
+}
+
+/// Generate a level. The same seed and difficulty always generate the same level.
+pub fn generate(seed: u64, difficulty: &Difficulty) -> MapSource {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut source = MapSource {
+        directives: vec![(0, format!(".comment Generated from the seed {}", seed))],
+        columns: vec![],
+    };
+
+    // The most rows the player can jump up to a platform across the narrowest gap.
+    let max_rise = (1..)
+        .take_while(|&rows| {
+            jump_reach(rows as f32 * ROW_HEIGHT, GRAVITY).is_some_and(|reach| reach >= gap_width(0))
+        })
+        .last()
+        .unwrap_or(0);
+
+    let mut height = 0;
+    let mut enemies = 0;
+
+    for platform in 0..difficulty.platforms {
+        if platform > 0 {
+            // Head for a random height, climbing only as much as a jump allows.
+            let target = rng.gen_range(-difficulty.max_height..=difficulty.max_height);
+            let step = (target - height).min(max_rise);
+
+            let reach = jump_reach(step as f32 * ROW_HEIGHT, GRAVITY).unwrap();
+            let widest = (0..)
+                .take_while(|&spaces| gap_width(spaces) <= reach.min(WIDEST_GAP) * difficulty.gap)
+                .last()
+                .unwrap_or(0);
+
+            height += step;
+
+            // Platforms on the same height are always apart, otherwise they look like one.
+            let narrowest = if step == 0 { widest.min(1) } else { 0 };
+
+            for _ in 0..rng.gen_range(narrowest..=widest) {
+                source.columns.push(('_', height));
+            }
+        }
+
+        let (shortest, longest) = difficulty.platform_length;
+        let length = rng.gen_range(shortest..=longest.max(shortest));
+
+        source.columns.push(('[', height));
+
+        for tile in 0..length {
+            let glyph = if platform == 0 && tile == 0 {
+                '4'
+            } else if platform > 0 && rng.gen_bool(difficulty.enemy_chance as f64) {
+                enemies += 1;
+                '8'
+            } else if rng.gen_bool(difficulty.barrel_chance as f64) {
+                '*'
+            } else {
+                '-'
+            };
+
+            source.columns.push((glyph, height));
+        }
+
+        // There has to be something to win against.
+        if platform + 1 == difficulty.platforms && enemies == 0 {
+            enemies += 1;
+            source.columns.push(('8', height));
+        }
+
+        source.columns.push((']', height));
+    }
+
+    let ammo = (enemies * AMMO_PER_ENEMY).max(Player::AMMO as u32);
+    source
+        .directives
+        .push((0, format!(".player_ammo {}", ammo)));
+
+    source
+}
+
+/// An endless run: levels generated one after another, each harder than the last.
+pub struct Endless {
+    pub seed: u64,
+    /// The number of levels already won in this run.
+    pub stage: u32,
+}
+
+impl Endless {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, stage: 0 }
+    }
+
+    /// Generate the level of the current stage, with the enemies of the provided roster.
+    pub fn level(&self, roster: &Roster) -> Level {
+        let seed = self
+            .seed
+            .wrapping_add(u64::from(self.stage).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+
+        let mut source = generate(seed, &Difficulty::stage(self.stage));
+        source.directives.push((
+            0,
+            format!(
+                ".end Stage {} of the run {} cleared. Onwards!",
+                self.stage + 1,
+                self.seed
+            ),
+        ));
+
+        Level::parse(&source.to_text(), roster).expect("generated levels are always valid")
+    }
+}
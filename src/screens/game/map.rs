@@ -1,187 +1,207 @@
-//! Helper struct that helps to parse .map files made only for Call of Ferris
+//! Builds the game world out of a [`Level`] parsed from a .map file or imported from Tiled.
 //!
-//! # Map
-//! `[` => Create left tile \
-//! `-` => Create center tile \
-//! `]` => Create right tile \
-//! `_` => Increase draw x by 100.0 \
-//! `8` => Push a tile with a enemy \
-//! `4` => Create a tile with the player \
-//! `*` => Create a tile with a barrel \
-//!
-//! # Setter Syntax
-//! `.comment` => A comment \
-//! `.using_weapon` => Set the current weapon \
-//! `.end` => The end quote displayed on the win screen
+//! See [`call_of_ferris::level`] for the .map syntax and how Tiled maps are imported.
 
-use ggez::Context;
+use call_of_ferris::level::{Level, LEVEL_EXTENSIONS};
+use ggez::{GameError, GameResult};
 
 use crate::{
     game::components::{
         barrel::Barrel,
-        bullet::WeaponType,
+        bullet::{EnemyBullet, WeaponType},
         enemy::Enemy,
+        platform::Platform,
         player::Player,
-        tile::{Tile, TileType},
+        tile::Tile,
     },
     game::physics::Physics,
     utils::AssetManager,
 };
 
+/// How far below the lowest tile the player can fall before dying.
+const FALL_DEPTH: f32 = 400.0;
+
+/// Load the level with the provided id from `/maps/`, in the first of the [`LEVEL_EXTENSIONS`]
+/// it exists in.
+pub fn load_level(map_id: &str, asset_manager: &AssetManager) -> GameResult<Level> {
+    let path = LEVEL_EXTENSIONS
+        .iter()
+        .map(|extension| (extension, format!("/maps/{}.{}", map_id, extension)))
+        .find(|(_, path)| asset_manager.has_file(path));
+
+    let (extension, path) = path.ok_or_else(|| {
+        GameError::ResourceLoadError(format!("there is no map called {}", map_id))
+    })?;
+
+    Level::from_source(
+        extension,
+        &asset_manager.get_file(&path),
+        asset_manager.roster(),
+    )
+    .map_err(|e| GameError::ResourceLoadError(format!("{}:{}", path, e)))
+}
+
 pub struct Map {
     pub ground: Vec<Tile>,
+    pub platforms: Vec<Platform>,
     pub enemies: Vec<Enemy>,
+    /// The bullets the enemies shot that are still flying.
+    pub enemy_bullets: Vec<EnemyBullet>,
     pub barrels: Vec<Barrel>,
     pub player: Player,
 
     pub total_enemies: i32,
+    /// The points scored for the enemies destroyed so far.
+    pub score: u32,
+
+    /// Falling below this Y position in the world kills the player.
+    pub death_y: f32,
 
     pub end: Option<String>,
     pub using: Option<(String, f32)>,
 
     pub weapon: WeaponType,
+
+    /// The music played in a loop during the level.
+    pub music: Option<String>,
+    /// The image drawn behind the level instead of the black sky.
+    pub background: Option<String>,
 }
 
 impl Map {
-    pub fn parse(
-        ctx: &mut Context,
-        map_id: &str,
-        physics: &mut Physics,
-        asset_manager: &AssetManager,
-    ) -> Self {
-        let map = asset_manager.get_file(format!("/maps/{}.map", map_id).as_str());
-
-        let mut draw_pos = 0.;
-
-        #[allow(unused_assignments)]
-        let mut draw_inc = 64.;
-
-        let mut ground = vec![];
-        let mut enemies = vec![];
-        let mut total_enemies = 0;
-        let mut barrels = vec![];
-
-        let mut player = None;
-
-        let mut end = None;
-        let mut using = None;
-
-        let mut weapon = WeaponType::Turbofish;
-
-        for line in map.split('\n').collect::<Vec<_>>() {
-            let exp = line.split(' ').collect::<Vec<_>>();
-
-            if exp[0].starts_with(".end") {
-                end = Some(exp[1..].join(" "));
-            } else if exp[0].starts_with(".using_weapon") {
-                let using_weapon = (exp[1..].join(" ").trim().to_string(), 1.0);
-
-                weapon = match using_weapon.0.as_str() {
-                    "Turbofish Gun" => WeaponType::Turbofish,
-                    "Grappling Gun" => WeaponType::Grappling,
-                    _ => panic!(""),
-                };
-
-                using = Some(using_weapon);
-            } else if exp[0].starts_with(".comment") {
-                // Do nothing. ¯\_(ツ)_/¯
-            } else {
-                for id in line.chars() {
-                    match id {
-                        '[' => {
-                            let tile =
-                                Tile::new(ctx, draw_pos, physics, asset_manager, TileType::Left);
-
-                            draw_inc = (tile.dimensions().x / 2.0) + 32.0;
-                            draw_pos += draw_inc;
-
-                            ground.push(tile);
-                        }
-
-                        '-' => {
-                            let tile =
-                                Tile::new(ctx, draw_pos, physics, asset_manager, TileType::Center);
-
-                            draw_inc = (tile.dimensions().x / 2.0) + 32.0;
-                            draw_pos += draw_inc;
-
-                            ground.push(tile);
-                        }
-
-                        ']' => {
-                            let tile = Tile::new(
-                                ctx,
-                                (draw_pos - 32.0) + 20.0,
-                                physics,
-                                asset_manager,
-                                TileType::Right,
-                            );
-
-                            draw_inc = (tile.dimensions().x / 2.0) + 32.0;
-                            draw_pos += draw_inc;
-
-                            ground.push(tile);
-                        }
-
-                        '_' => {
-                            draw_inc = 100.0;
-                            draw_pos += draw_inc;
-                        }
-
-                        '8' => {
-                            let tile =
-                                Tile::new(ctx, draw_pos, physics, asset_manager, TileType::Center);
-
-                            draw_inc = (tile.dimensions().x / 2.0) + 32.0;
-
-                            ground.push(tile);
-                            enemies.push(Enemy::new(ctx, draw_pos, physics, asset_manager));
-
-                            draw_pos += draw_inc;
-                            total_enemies += 1;
-                        }
-
-                        '4' => {
-                            let tile =
-                                Tile::new(ctx, draw_pos, physics, asset_manager, TileType::Center);
-
-                            player = Some(Player::new(ctx, draw_pos, physics, asset_manager));
+    /// Create the physics world for the level, with the level's own gravity if it has one.
+    pub fn physics(level: &Level) -> Physics {
+        Physics::with_gravity(level.tuning.gravity.unwrap_or(Physics::GRAVITY))
+    }
 
-                            draw_inc = tile.dimensions().x;
-                            draw_pos += draw_inc;
+    /// Check that the music and the background the level asks for exist.
+    pub fn check_assets(level: &Level, asset_manager: &AssetManager) -> GameResult<()> {
+        if let Some(music) = &level.tuning.music {
+            if !asset_manager.has_sound(music) {
+                return Err(GameError::ResourceLoadError(format!(
+                    "the level plays the music {} but there is no /audio/{}",
+                    music, music
+                )));
+            }
+        }
 
-                            ground.push(tile);
-                        }
+        if let Some(background) = &level.tuning.background {
+            if !asset_manager.has_image(background) {
+                return Err(GameError::ResourceLoadError(format!(
+                    "the level draws the background {} but there is no /images/{}",
+                    background, background
+                )));
+            }
+        }
 
-                        '*' => {
-                            let tile =
-                                Tile::new(ctx, draw_pos, physics, asset_manager, TileType::Center);
+        Ok(())
+    }
 
-                            draw_inc = tile.dimensions().x;
+    /// Create the bodies of everything in the level.
+    pub fn build(level: &Level, physics: &mut Physics, asset_manager: &AssetManager) -> Self {
+        let ground = level
+            .tiles
+            .iter()
+            .map(|(tile_type, spawn)| {
+                Tile::new(
+                    spawn.position.x,
+                    spawn.position.y,
+                    physics,
+                    asset_manager,
+                    *tile_type,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let platforms = level
+            .platforms
+            .iter()
+            .map(|(motion, spawn)| {
+                Platform::new(
+                    spawn.position.x,
+                    spawn.position.y,
+                    physics,
+                    asset_manager,
+                    motion.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let enemies = level
+            .enemies
+            .iter()
+            .map(|(kind, behaviour, spawn)| {
+                let kind = asset_manager
+                    .roster()
+                    .kind(kind)
+                    .expect("the level was loaded with the same roster");
+
+                Enemy::new(
+                    spawn.position.x,
+                    spawn.position.y,
+                    physics,
+                    asset_manager,
+                    kind,
+                    behaviour.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let barrels = level
+            .barrels
+            .iter()
+            .map(|spawn| Barrel::new(spawn.position.x, spawn.position.y, physics, asset_manager))
+            .collect::<Vec<_>>();
+
+        let mut player = Player::new(
+            level.player.position.x,
+            level.player.position.y,
+            physics,
+            asset_manager,
+        );
+
+        if let Some(ammo) = level.tuning.player_ammo {
+            player.ammo = ammo as f32;
+            player.max_ammo = ammo as f32;
+        }
 
-                            ground.push(tile);
-                            barrels.push(Barrel::new(ctx, draw_pos, physics, asset_manager));
+        if let Some(health) = level.tuning.player_health {
+            player.health = health;
+            player.max_health = health;
+        }
 
-                            draw_pos += draw_inc;
-                        }
+        player.double_jump = level.tuning.double_jump.unwrap_or(false);
 
-                        _ => {}
-                    }
-                }
-            }
-        }
+        // The lowest tile in the map, the player dies when falling too far below it.
+        let lowest_y = level
+            .tiles
+            .iter()
+            .map(|(_, spawn)| spawn.position.y)
+            .fold(level.player.position.y, f32::max);
 
-        let player = player.unwrap();
+        let weapon = match level.weapon.as_str() {
+            "Turbofish Gun" => WeaponType::Turbofish,
+            "Grappling Gun" => WeaponType::Grappling,
+            _ => unreachable!("the level parser only accepts known weapons"),
+        };
 
         Self {
+            total_enemies: enemies.len() as i32,
+            score: 0,
+
             ground,
+            platforms,
             enemies,
+            enemy_bullets: vec![],
             barrels,
             player,
-            total_enemies,
-            end,
-            using,
+            death_y: lowest_y + FALL_DEPTH,
+            end: level.end.clone(),
+            using: Some((level.weapon.clone(), 1.0)),
             weapon,
+
+            music: level.tuning.music.clone(),
+            background: level.tuning.background.clone(),
         }
     }
 }
@@ -1,6 +1,14 @@
+pub mod campaign;
 mod components;
+pub mod debug;
+#[allow(clippy::module_inception)]
 mod game;
+pub mod generator;
 mod map;
 pub mod physics;
+#[cfg(test)]
+mod tests;
 
+pub use components::{platform::Platform, tile::Tile};
 pub use game::*;
+pub use map::load_level;
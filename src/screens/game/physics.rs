@@ -1,33 +1,33 @@
 //! This file contains a helper physics struct and a bunch of helper conversion methods.
 
-use ggez::graphics::Color;
-#[cfg(feature = "debug")]
 use ggez::{
-    graphics::{self, DrawParam, Rect},
+    graphics::{self, Color, DrawMode, DrawParam, Rect},
     nalgebra::Point2,
     Context, GameResult,
 };
-
-#[cfg(feature = "debug")]
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 
-use ncollide2d::{pipeline::CollisionGroups, query::RayIntersection};
-use nphysics2d::{
-    material,
-    nalgebra::{Isometry2, Vector2},
-    ncollide2d::{
-        self,
-        query::{ContactManifold, Ray},
-        shape::{Cuboid, ShapeHandle},
+use call_of_ferris::level;
+use rapier2d::{
+    dynamics::{
+        CCDSolver, CoefficientCombineRule, IntegrationParameters, IslandManager, JointSet,
+        RigidBody, RigidBodyBuilder, RigidBodyHandle, RigidBodySet, RigidBodyType,
+    },
+    geometry::{
+        BroadPhase, Collider, ColliderBuilder, ColliderHandle, ColliderSet, ContactEvent,
+        ContactPair, InteractionGroups, IntersectionEvent, NarrowPhase, Ray, RayIntersection,
     },
-    object::{
-        self, BodyPartHandle, BodyStatus, ColliderDesc, DefaultBodyHandle, RigidBody, RigidBodyDesc,
+    na::{self, Isometry2, Vector2},
+    parry,
+    pipeline::{
+        ActiveEvents, ActiveHooks, ContactModificationContext, EventHandler, PhysicsHooks,
+        PhysicsPipeline, QueryPipeline,
     },
-    world,
 };
 
-use nphysics2d::nalgebra as na;
-use object::Collider;
+use super::debug::{DebugOverlay, DebugView};
+
+use std::{collections::HashMap, ops::BitOr, sync::Mutex};
 
 type N = f32;
 
@@ -40,6 +40,10 @@ pub enum ObjectData {
     Bullet,
     Barrel,
     Particle(Color),
+    /// A spike tile, ground that hurts.
+    Spikes,
+    /// A bullet shot by an enemy at the player, with the health the player loses when it hits.
+    EnemyBullet(u32),
 }
 
 impl ObjectData {
@@ -51,357 +55,1047 @@ impl ObjectData {
     }
 }
 
-/// Helper physics struct that makes lives easier while using nphysics2d physics engine with ggez.
-pub struct Physics {
-    mechanical_world: world::DefaultMechanicalWorld<N>,
-    geometrical_world: world::DefaultGeometricalWorld<N>,
-    body_set: object::DefaultBodySet<N>,
-    collider_set: object::DefaultColliderSet<N>,
-    joint_constraint_set: nphysics2d::joint::DefaultJointConstraintSet<N>,
-    force_generator_set: nphysics2d::force_generator::DefaultForceGeneratorSet<N>,
+/// Rapier colliders carry a `u128` of user data. The low byte is the kind of object, a particle
+/// keeps its color in the bytes above it and an enemy bullet its damage.
+impl From<ObjectData> for u128 {
+    fn from(data: ObjectData) -> Self {
+        match data {
+            ObjectData::Ground => 0,
+            ObjectData::Player => 1,
+            ObjectData::Enemy => 2,
+            ObjectData::Bullet => 3,
+            ObjectData::Barrel => 4,
+            ObjectData::Spikes => 6,
+            ObjectData::EnemyBullet(damage) => 7 | (damage as u128) << 8,
+            ObjectData::Particle(color) => {
+                let (r, g, b, a) = color.to_rgba();
+
+                5 | (u32::from_be_bytes([r, g, b, a]) as u128) << 8
+            }
+        }
+    }
 }
 
-impl Physics {
-    // TODO: Move the separate rigid body creator functions to use the create_rigid_body() and create_collider() functions in dead.
+impl From<u128> for ObjectData {
+    fn from(data: u128) -> Self {
+        match data & 0xff {
+            0 => ObjectData::Ground,
+            1 => ObjectData::Player,
+            2 => ObjectData::Enemy,
+            3 => ObjectData::Bullet,
+            4 => ObjectData::Barrel,
+            5 => {
+                let [r, g, b, a] = ((data >> 8) as u32).to_be_bytes();
 
-    /// The amount of gravity for the Y axis in the physics world.
-    const GRAVITY: N = 300.0;
+                ObjectData::Particle(Color::from_rgba(r, g, b, a))
+            }
+            6 => ObjectData::Spikes,
+            7 => ObjectData::EnemyBullet((data >> 8) as u32),
+            _ => unreachable!("Every collider is created with an ObjectData"),
+        }
+    }
+}
 
-    /// Create a new physics struct object.
-    pub fn new() -> Self {
-        let geometrical_world = world::DefaultGeometricalWorld::new();
-        let gravity = Self::GRAVITY;
+/// The named collision layers. Every collider is on one of them, and the [`CollisionMatrix`]
+/// decides which layers collide with each other.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Layer {
+    /// The tiles, and what stands around on them like the barrels.
+    Ground,
+    Player,
+    Enemy,
+    /// Bullets, from the player or from the enemies.
+    Projectile,
+    Particle,
+    /// Things the player picks up by walking into them.
+    Pickup,
+    /// Areas that do something when the player walks into them.
+    Trigger,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 7] = [
+        Layer::Ground,
+        Layer::Player,
+        Layer::Enemy,
+        Layer::Projectile,
+        Layer::Particle,
+        Layer::Pickup,
+        Layer::Trigger,
+    ];
+
+    /// The layer that bodies of the provided kind are on, unless their spec says otherwise.
+    pub fn of(data: ObjectData) -> Self {
+        match data {
+            ObjectData::Ground | ObjectData::Barrel | ObjectData::Spikes => Layer::Ground,
+            ObjectData::Player => Layer::Player,
+            ObjectData::Enemy => Layer::Enemy,
+            ObjectData::Bullet | ObjectData::EnemyBullet(_) => Layer::Projectile,
+            ObjectData::Particle(_) => Layer::Particle,
+        }
+    }
+}
+
+/// A set of layers, made by combining them with `|`.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct LayerMask(u32);
+
+impl LayerMask {
+    pub const ALL: LayerMask = LayerMask(u32::MAX);
+    pub const NONE: LayerMask = LayerMask(0);
+
+    pub fn contains(self, layer: Layer) -> bool {
+        self.0 & LayerMask::from(layer).0 != 0
+    }
+}
+
+impl From<Layer> for LayerMask {
+    fn from(layer: Layer) -> Self {
+        LayerMask(1 << layer as u32)
+    }
+}
+
+impl<T: Into<LayerMask>> BitOr<T> for LayerMask {
+    type Output = LayerMask;
+
+    fn bitor(self, other: T) -> LayerMask {
+        LayerMask(self.0 | other.into().0)
+    }
+}
 
-        let mechanical_world = world::DefaultMechanicalWorld::new(Vector2::new(0.0, gravity));
+impl<T: Into<LayerMask>> BitOr<T> for Layer {
+    type Output = LayerMask;
 
-        let body_set = object::DefaultBodySet::new();
-        let collider_set = object::DefaultColliderSet::new();
+    fn bitor(self, other: T) -> LayerMask {
+        LayerMask::from(self) | other
+    }
+}
 
-        let joint_constraint_set = nphysics2d::joint::DefaultJointConstraintSet::new();
-        let force_generator_set = nphysics2d::force_generator::DefaultForceGeneratorSet::new();
+/// Which layers collide with each other. Layers that do not collide go through each other and
+/// never show up in each other's contacts.
+#[derive(Debug, Clone)]
+pub struct CollisionMatrix {
+    masks: [LayerMask; 7],
+}
 
+impl CollisionMatrix {
+    /// A matrix where no layer collides with any other.
+    pub fn empty() -> Self {
         Self {
-            mechanical_world,
-            geometrical_world,
-            body_set,
-            collider_set,
-            joint_constraint_set,
-            force_generator_set,
+            masks: [LayerMask::NONE; 7],
         }
     }
 
-    /// Step the physics world.
-    pub fn step(&mut self) {
-        self.mechanical_world.step(
-            &mut self.geometrical_world,
-            &mut self.body_set,
-            &mut self.collider_set,
-            &mut self.joint_constraint_set,
-            &mut self.force_generator_set,
-        );
+    /// Make the two layers collide or go through each other.
+    pub fn set(&mut self, a: Layer, b: Layer, collide: bool) {
+        for (layer, other) in [(a, b), (b, a)] {
+            let mask = &mut self.masks[layer as usize];
+
+            *mask = if collide {
+                *mask | other
+            } else {
+                LayerMask(mask.0 & !LayerMask::from(other).0)
+            };
+        }
     }
 
-    // Creates a new tile body.
-    pub fn create_tile(
-        &mut self,
-        pos: na::Point2<f32>,
-        width: u16,
-        height: u16,
-    ) -> DefaultBodyHandle {
-        let width = width as f32;
-        let height = height as f32;
-
-        let ground = RigidBodyDesc::new()
-            .position(point_to_isometry(pos))
-            .status(BodyStatus::Static)
-            .build();
-        let ground_handle = self.body_set.insert(ground);
+    pub fn collides(&self, a: Layer, b: Layer) -> bool {
+        self.masks[a as usize].contains(b)
+    }
 
-        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(
-            width / 2.0 - 0.01,
-            height / 2.0 - 0.01,
-        )));
-        let collider = ColliderDesc::new(shape)
-            .material(material::MaterialHandle::new(material::BasicMaterial::new(
-                0.0, 0.0,
-            )))
-            .user_data(ObjectData::Ground)
-            .build(BodyPartHandle(ground_handle, 0));
+    /// The layers that the provided one collides with.
+    pub fn mask(&self, layer: Layer) -> LayerMask {
+        self.masks[layer as usize]
+    }
 
-        self.collider_set.insert(collider);
+    fn groups(&self, layer: Layer) -> InteractionGroups {
+        InteractionGroups::new(LayerMask::from(layer).0, self.mask(layer).0)
+    }
+}
 
-        ground_handle
+impl Default for CollisionMatrix {
+    /// Everything stands on the ground, but particles only bounce off of it, bullets go through
+    /// each other, and pickups and triggers are only there for the player.
+    fn default() -> Self {
+        let mut matrix = Self::empty();
+
+        for layer in Layer::ALL {
+            if layer != Layer::Trigger {
+                matrix.set(Layer::Ground, layer, true);
+            }
+        }
+
+        matrix.set(Layer::Player, Layer::Enemy, true);
+        matrix.set(Layer::Player, Layer::Projectile, true);
+        matrix.set(Layer::Player, Layer::Pickup, true);
+        matrix.set(Layer::Player, Layer::Trigger, true);
+        matrix.set(Layer::Enemy, Layer::Enemy, true);
+        matrix.set(Layer::Enemy, Layer::Projectile, true);
+
+        matrix
     }
+}
 
-    /// Create a new player body.
-    pub fn create_player(
-        &mut self,
-        pos: na::Point2<f32>,
-        width: u16,
-        height: u16,
-    ) -> DefaultBodyHandle {
-        let width = width as f32;
-        let height = height as f32;
-
-        let player = RigidBodyDesc::new()
-            .position(point_to_isometry(pos))
-            .mass(10.0)
-            .linear_damping(1.0)
-            .status(BodyStatus::Dynamic)
-            .build();
-        let player_handle = self.body_set.insert(player);
+/// The shape of the collider of a body, centred on the position of the body.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    /// A box, usually the size of the sprite.
+    Cuboid {
+        width: N,
+        height: N,
+    },
+    Ball {
+        radius: N,
+    },
+    /// A standing pill, `height` tall from the top of its top cap to the bottom of its bottom cap.
+    Capsule {
+        height: N,
+        radius: N,
+    },
+    /// The convex hull of the points, relative to the position of the body.
+    Convex(Vec<na::Point2<N>>),
+}
 
-        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(
-            width / 2.0 - 0.01,
-            height / 2.0 - 0.01,
-        )));
-        let collider = ColliderDesc::new(shape)
-            .material(material::MaterialHandle::new(material::BasicMaterial::new(
-                0.0, 0.0,
-            )))
-            .user_data(ObjectData::Player)
-            .build(BodyPartHandle(player_handle, 0));
+impl Shape {
+    /// A box the size of an image, see [`AssetManager::image_size`]. Which is what most of the
+    /// bodies are.
+    ///
+    /// [`AssetManager::image_size`]: crate::utils::AssetManager::image_size
+    pub fn sprite((width, height): (u16, u16)) -> Self {
+        Shape::Cuboid {
+            width: width as N,
+            height: height as N,
+        }
+    }
+}
 
-        self.collider_set.insert(collider);
+/// Everything needed to create a body with [`Physics::create_body`].
+///
+/// A spec starts out as a dynamic body with no rotation, a mass of 1, and no damping, friction
+/// or bounce, on the layer of its kind of object, see [`Layer::of`]. The builder methods change that.
+#[derive(Debug, Clone)]
+pub struct BodySpec {
+    data: ObjectData,
+    shape: Shape,
+    body_type: RigidBodyType,
+    position: na::Point2<N>,
+    mass: N,
+    damping: N,
+    friction: N,
+    restitution: N,
+    sensor: bool,
+    one_way: bool,
+    layer: Layer,
+    ccd: bool,
+    rotates: bool,
+    gravity_scale: N,
+}
 
-        player_handle
+impl BodySpec {
+    /// A body that falls and gets pushed around.
+    pub fn dynamic(data: ObjectData, shape: Shape) -> Self {
+        Self {
+            data,
+            shape,
+            body_type: RigidBodyType::Dynamic,
+            position: na::Point2::origin(),
+            mass: 1.0,
+            damping: 0.0,
+            friction: 0.0,
+            restitution: 0.0,
+            sensor: false,
+            one_way: false,
+            layer: Layer::of(data),
+            ccd: false,
+            rotates: false,
+            gravity_scale: 1.0,
+        }
     }
 
-    /// Create a new enemy body.
-    pub fn create_enemy(
-        &mut self,
-        pos: na::Point2<f32>,
-        width: u16,
-        height: u16,
-    ) -> DefaultBodyHandle {
-        let width = width as f32;
-        let height = height as f32;
-
-        let enemy = RigidBodyDesc::new()
-            .position(point_to_isometry(pos))
-            .mass(10.0)
-            .linear_damping(1.0)
-            .status(BodyStatus::Dynamic)
-            .build();
-        let enemy_handle = self.body_set.insert(enemy);
+    /// A body that never moves, like the ground.
+    pub fn fixed(data: ObjectData, shape: Shape) -> Self {
+        Self {
+            body_type: RigidBodyType::Static,
+            ..Self::dynamic(data, shape)
+        }
+    }
 
-        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(
-            width / 2.0 - 0.01,
-            height / 2.0 - 0.01,
-        )));
-        let collider = ColliderDesc::new(shape)
-            .material(material::MaterialHandle::new(material::BasicMaterial::new(
-                0.0, 0.0,
-            )))
-            .user_data(ObjectData::Enemy)
-            .build(BodyPartHandle(enemy_handle, 0));
+    /// A body that is only moved by the game, by setting its position, and pushes the dynamic
+    /// bodies out of its way.
+    pub fn kinematic(data: ObjectData, shape: Shape) -> Self {
+        Self {
+            body_type: RigidBodyType::KinematicPositionBased,
+            ..Self::dynamic(data, shape)
+        }
+    }
 
-        self.collider_set.insert(collider);
+    /// Where the centre of the body starts in the world.
+    pub fn position(mut self, position: na::Point2<N>) -> Self {
+        self.position = position;
+        self
+    }
 
-        enemy_handle
+    pub fn mass(mut self, mass: N) -> Self {
+        self.mass = mass;
+        self
     }
 
-    /// Create a new enemy body.
-    pub fn create_barrel(
-        &mut self,
-        pos: na::Point2<f32>,
-        width: u16,
-        height: u16,
-    ) -> DefaultBodyHandle {
-        let width = width as f32;
-        let height = height as f32;
-
-        let barrel = RigidBodyDesc::new()
-            .position(point_to_isometry(pos))
-            .mass(10.0)
-            .linear_damping(1.0)
-            .status(BodyStatus::Dynamic)
-            .build();
-        let barrel_handle = self.body_set.insert(barrel);
+    /// How much of its velocity the body loses over time, see [`RigidBodyBuilder::linear_damping`].
+    pub fn damping(mut self, damping: N) -> Self {
+        self.damping = damping;
+        self
+    }
 
-        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(
-            width / 2.0 - 0.01,
-            height / 2.0 - 0.01,
-        )));
-        let collider = ColliderDesc::new(shape)
-            .material(material::MaterialHandle::new(material::BasicMaterial::new(
-                0.0, 0.0,
-            )))
-            .user_data(ObjectData::Barrel)
-            .build(BodyPartHandle(barrel_handle, 0));
+    pub fn friction(mut self, friction: N) -> Self {
+        self.friction = friction;
+        self
+    }
 
-        self.collider_set.insert(collider);
+    /// How much the body bounces back, from 0 to 1. The bounciest of two bodies decides how much
+    /// they bounce off each other.
+    pub fn restitution(mut self, restitution: N) -> Self {
+        self.restitution = restitution;
+        self
+    }
 
-        barrel_handle
+    /// A sensor notices what overlaps it without pushing it away.
+    pub fn sensor(mut self, sensor: bool) -> Self {
+        self.sensor = sensor;
+        self
     }
 
-    /// Create a new bullet. Can be any included in crate::components::bullet::PlayerWeapon enum
-    pub fn create_bullet(
-        &mut self,
-        pos: na::Point2<f32>,
-        width: u16,
-        height: u16,
-    ) -> DefaultBodyHandle {
-        let width = width as f32;
-        let height = height as f32;
-
-        let bullet = RigidBodyDesc::new()
-            .position(point_to_isometry(pos))
-            .mass(10.0)
-            .linear_damping(1.0)
-            .status(BodyStatus::Dynamic)
-            .build();
-        let bullet_handle = self.body_set.insert(bullet);
+    /// Only stop what comes from above, like a platform that can be jumped through from below.
+    pub fn one_way(mut self, one_way: bool) -> Self {
+        self.one_way = one_way;
+        self
+    }
+
+    /// The layer the body is on, which decides what it collides with.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layer = layer;
+        self
+    }
 
-        let shape = ShapeHandle::new(Cuboid::new(Vector2::new(
-            width / 2.0 - 0.01,
-            height / 2.0 - 0.01,
-        )));
-        let collider = ColliderDesc::new(shape)
-            .material(material::MaterialHandle::new(material::BasicMaterial::new(
-                0.0, 0.0,
-            )))
-            .user_data(ObjectData::Bullet)
-            .build(BodyPartHandle(bullet_handle, 0));
+    /// Continuous collision detection, so that a fast body can not go through thin things in a
+    /// single step.
+    pub fn ccd(mut self, ccd: bool) -> Self {
+        self.ccd = ccd;
+        self
+    }
 
-        self.collider_set.insert(collider);
+    /// Let the body tip over and roll instead of always staying upright.
+    pub fn rotates(mut self, rotates: bool) -> Self {
+        self.rotates = rotates;
+        self
+    }
 
-        bullet_handle
+    /// How much the gravity pulls the body, 0 for it to fly straight.
+    pub fn gravity_scale(mut self, gravity_scale: N) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
     }
+}
 
-    /// Create a new rigid body
-    pub fn create_rigid_body(&mut self, body: RigidBody<f32>) -> DefaultBodyHandle {
-        let handle = self.body_set.insert(body);
+/// What happened between the two bodies of a [`Contact`].
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ContactKind {
+    /// The bodies started touching.
+    Started,
+    /// The bodies stopped touching.
+    Stopped,
+    /// Something got inside of a sensor.
+    SensorEntered,
+    /// Something left a sensor.
+    SensorExited,
+}
 
-        handle
+/// A change in what touches what during a step of the physics world, see [`Physics::drain_contacts`].
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub kind: ContactKind,
+    pub first: (RigidBodyHandle, ObjectData),
+    pub second: (RigidBodyHandle, ObjectData),
+}
+
+impl Contact {
+    /// The body on the other side of the contact and its user data, if the provided body is one
+    /// of the two.
+    pub fn other(&self, body: RigidBodyHandle) -> Option<(RigidBodyHandle, ObjectData)> {
+        if self.first.0 == body {
+            Some(self.second)
+        } else if self.second.0 == body {
+            Some(self.first)
+        } else {
+            None
+        }
     }
 
-    /// Create a new collider
-    pub fn create_collider(&mut self, collider: Collider<f32, DefaultBodyHandle>) {
-        self.collider_set.insert(collider);
+    /// Whether the provided body started touching something of the provided kind, which gets
+    /// returned.
+    pub fn started_with(&self, body: RigidBodyHandle, data: ObjectData) -> Option<RigidBodyHandle> {
+        match self.other(body) {
+            Some((other, other_data))
+                if self.kind == ContactKind::Started && other_data == data =>
+            {
+                Some(other)
+            }
+            _ => None,
+        }
     }
+}
 
-    /// Returns a immutable body from the handle provided by the above helper functions.
-    pub fn get_rigid_body(&mut self, handle: DefaultBodyHandle) -> &RigidBody<f32> {
-        let body = self.body_set.rigid_body(handle).expect("Body not found!");
+/// Lets the bodies through the one-way bodies, unless they come from above.
+struct OneWayHooks;
+
+impl OneWayHooks {
+    /// How far from straight down something can land on a one-way body, in radians.
+    const LANDING_ANGLE: N = std::f32::consts::FRAC_PI_4;
+}
 
-        body
+impl PhysicsHooks<RigidBodySet, ColliderSet> for OneWayHooks {
+    fn modify_solver_contacts(
+        &self,
+        context: &mut ContactModificationContext<RigidBodySet, ColliderSet>,
+    ) {
+        let one_way = |collider: ColliderHandle| {
+            context.colliders[collider]
+                .active_hooks()
+                .contains(ActiveHooks::MODIFY_SOLVER_CONTACTS)
+        };
+
+        // The normal points out of the first collider, up out of a one-way body and down into it
+        // from the other side. Y grows downwards.
+        let allowed_normal = if one_way(context.collider1) {
+            Vector2::new(0.0, -1.0)
+        } else {
+            Vector2::new(0.0, 1.0)
+        };
+
+        context.update_as_oneway_platform(&allowed_normal, Self::LANDING_ANGLE);
     }
+}
 
-    /// Returns a mutable body from the handle provided by the above helper functions.
-    pub fn get_rigid_body_mut(&mut self, handle: DefaultBodyHandle) -> &mut RigidBody<f32> {
-        let body = self
-            .body_set
-            .rigid_body_mut(handle)
-            .expect("Body not found!");
+/// Collects the events of rapier while the world steps, as the colliders they are about.
+#[derive(Default)]
+struct EventCollector(Mutex<Vec<(ContactKind, ColliderHandle, ColliderHandle)>>);
+
+impl EventHandler for EventCollector {
+    fn handle_intersection_event(&self, event: IntersectionEvent) {
+        let kind = if event.intersecting {
+            ContactKind::SensorEntered
+        } else {
+            ContactKind::SensorExited
+        };
+
+        self.0
+            .lock()
+            .unwrap()
+            .push((kind, event.collider1, event.collider2));
+    }
+
+    fn handle_contact_event(&self, event: ContactEvent, _: &ContactPair) {
+        let event = match event {
+            ContactEvent::Started(collider1, collider2) => {
+                (ContactKind::Started, collider1, collider2)
+            }
+            ContactEvent::Stopped(collider1, collider2) => {
+                (ContactKind::Stopped, collider1, collider2)
+            }
+        };
+
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+/// A ray cast kept for the debug overlay to draw.
+struct RecordedRay {
+    origin: na::Point2<N>,
+    /// Where the ray hit something, or far away if it did not.
+    end: na::Point2<N>,
+    /// For how many seconds it was shown.
+    shown: N,
+}
+
+/// Helper physics struct that makes lives easier while using rapier physics engine with ggez.
+pub struct Physics {
+    gravity: Vector2<N>,
+    integration_parameters: IntegrationParameters,
+    pipeline: PhysicsPipeline,
+    island_manager: IslandManager,
+    broad_phase: BroadPhase,
+    narrow_phase: NarrowPhase,
+    body_set: RigidBodySet,
+    collider_set: ColliderSet,
+    joint_set: JointSet,
+    ccd_solver: CCDSolver,
+    query_pipeline: QueryPipeline,
+    /// Whether bodies were created or destroyed since the query pipeline was last updated, so
+    /// that it is updated before the next ray cast.
+    query_outdated: bool,
+    matrix: CollisionMatrix,
+    events: EventCollector,
+    contacts: Vec<Contact>,
+    /// How much faster or slower than real time the world goes, see [`Physics::steps`].
+    time_scale: N,
+    /// The scaled time that passed but was not stepped yet, always less than a step.
+    accumulator: N,
+    /// How long the world has been stepped for, in seconds.
+    time: N,
+    /// Where the moving bodies were before the last step, to draw them between there and where
+    /// they are now.
+    previous: HashMap<RigidBodyHandle, na::Point2<N>>,
+    /// The rays cast lately, for the debug overlay. `None` unless they are recorded.
+    ray_casts: Option<Vec<RecordedRay>>,
+}
+
+impl Physics {
+    /// The amount of gravity for the Y axis in the physics world.
+    pub const GRAVITY: N = level::GRAVITY;
+
+    /// How much velocity the player body loses over time, see [`RigidBodyBuilder::linear_damping`].
+    pub const PLAYER_DAMPING: N = level::PLAYER_DAMPING;
+
+    /// How long every step of the physics world is, in seconds. The world always steps by the same
+    /// amount of time, so it plays out the same whatever the frame rate is.
+    pub const STEP: N = level::PHYSICS_STEP;
+
+    /// The most steps taken in a single frame, so that a long hitch does not freeze the game while
+    /// it catches up. The time past that is dropped.
+    const MAX_STEPS: u32 = 5;
+
+    /// How long the debug overlay shows a ray cast for, in seconds, so that the ones cast only
+    /// once can be seen too.
+    const RAY_CAST_LIFETIME: N = 0.2;
+    /// How far the debug overlay draws a ray that did not hit anything.
+    const RAY_CAST_LENGTH: N = 1000.0;
 
-        body
+    /// Create a new physics struct object.
+    pub fn new() -> Self {
+        Self::with_gravity(Self::GRAVITY)
+    }
+
+    /// Create a new physics struct object with its own amount of gravity for the Y axis.
+    pub fn with_gravity(gravity: N) -> Self {
+        Self {
+            gravity: Vector2::new(0.0, gravity),
+            integration_parameters: IntegrationParameters {
+                dt: Self::STEP,
+                ..IntegrationParameters::default()
+            },
+            pipeline: PhysicsPipeline::new(),
+            island_manager: IslandManager::new(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            body_set: RigidBodySet::new(),
+            collider_set: ColliderSet::new(),
+            joint_set: JointSet::new(),
+            ccd_solver: CCDSolver::new(),
+            query_pipeline: QueryPipeline::new(),
+            query_outdated: false,
+            matrix: CollisionMatrix::default(),
+            events: EventCollector::default(),
+            contacts: vec![],
+            time_scale: 1.0,
+            accumulator: 0.0,
+            time: 0.0,
+            previous: HashMap::new(),
+            ray_casts: None,
+        }
     }
 
-    /// Simple helper function that allows you to see the colliders.
-    /// To be able to show the colliders run Call of Ferris by `cargo run --features=["debug"]`
-    #[cfg(feature = "debug")]
-    pub fn draw_colliders(&self, ctx: &mut Context, camera: &Camera) -> GameResult {
+    /// Change which layers collide with each other, for the bodies already in the world too.
+    pub fn set_collision_matrix(&mut self, matrix: CollisionMatrix) {
+        for (_, collider) in self.collider_set.iter_mut() {
+            let layer = Self::layer_of(collider);
+
+            collider.set_collision_groups(matrix.groups(layer));
+        }
+
+        self.matrix = matrix;
+    }
+
+    /// Make the world go slower than real time, or faster. 1 is real time.
+    pub fn set_time_scale(&mut self, time_scale: N) {
+        self.time_scale = time_scale;
+    }
+
+    pub fn time_scale(&self) -> N {
+        self.time_scale
+    }
+
+    /// How long the world has been stepped for, in seconds. Goes slower with the time scale.
+    pub fn time(&self) -> N {
+        self.time
+    }
+
+    /// The amount of gravity for the Y axis in this world.
+    pub fn gravity(&self) -> N {
+        self.gravity.y
+    }
+
+    /// Let the provided real time in seconds pass, and return how many steps of the world are due
+    /// now for it to keep up. The caller takes them with [`Physics::step`].
+    pub fn steps(&mut self, delta: N) -> u32 {
+        if let Some(ray_casts) = &mut self.ray_casts {
+            ray_casts.retain(|ray| ray.shown < Self::RAY_CAST_LIFETIME);
+
+            for ray in ray_casts.iter_mut() {
+                ray.shown += delta;
+            }
+        }
+
+        self.accumulator += delta * self.time_scale;
+
+        let steps = (self.accumulator / Self::STEP) as u32;
+        self.accumulator -= steps as N * Self::STEP;
+
+        steps.min(Self::MAX_STEPS)
+    }
+
+    /// Step the physics world by [`Physics::STEP`].
+    pub fn step(&mut self) {
+        self.previous.clear();
+
+        for (handle, body) in self.body_set.iter() {
+            if !body.is_static() {
+                self.previous
+                    .insert(handle, isometry_to_point(body.position()));
+            }
+        }
+
+        self.pipeline.step(
+            &self.gravity,
+            &self.integration_parameters,
+            &mut self.island_manager,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.body_set,
+            &mut self.collider_set,
+            &mut self.joint_set,
+            &mut self.ccd_solver,
+            &OneWayHooks,
+            &self.events,
+        );
+
+        // The events about bodies that were destroyed before the step are dropped, the ones who
+        // destroyed them already know.
+        let collider_set = &self.collider_set;
+        let body_and_data = |collider| {
+            let collider: &Collider = collider_set.get(collider)?;
+
+            Some((collider.parent()?, ObjectData::from(collider.user_data)))
+        };
+
+        for (kind, collider1, collider2) in self.events.0.get_mut().unwrap().drain(..) {
+            if let (Some(first), Some(second)) =
+                (body_and_data(collider1), body_and_data(collider2))
+            {
+                self.contacts.push(Contact {
+                    kind,
+                    first,
+                    second,
+                });
+            }
+        }
+
+        self.time += Self::STEP;
+
+        // Keep the ray casts in sync with where the bodies moved to.
+        self.update_query_pipeline();
+    }
+
+    /// Bring the query pipeline, which the ray casts go through, up to date with the bodies.
+    fn update_query_pipeline(&mut self) {
+        self.query_pipeline
+            .update(&self.island_manager, &self.body_set, &self.collider_set);
+        self.query_outdated = false;
+    }
+
+    /// Take the contacts of the steps since the last time this was called. Called once every
+    /// frame, after stepping.
+    pub fn drain_contacts(&mut self) -> Vec<Contact> {
+        std::mem::take(&mut self.contacts)
+    }
+
+    /// Create a new body with a single collider, as described by the spec.
+    pub fn create_body(&mut self, spec: BodySpec) -> RigidBodyHandle {
+        let mut body = RigidBodyBuilder::new(spec.body_type)
+            .position(point_to_isometry(spec.position))
+            .additional_mass(spec.mass)
+            .linear_damping(spec.damping)
+            .gravity_scale(spec.gravity_scale)
+            .ccd_enabled(spec.ccd);
+
+        if !spec.rotates {
+            body = body.lock_rotations();
+        }
+
+        let handle = self.body_set.insert(body.build());
+
+        let collider = match spec.shape {
+            Shape::Cuboid { width, height } => ColliderBuilder::cuboid(width / 2.0, height / 2.0),
+            Shape::Ball { radius } => ColliderBuilder::ball(radius),
+            Shape::Capsule { height, radius } => {
+                ColliderBuilder::capsule_y((height / 2.0 - radius).max(0.0), radius)
+            }
+            Shape::Convex(points) => ColliderBuilder::convex_hull(&points)
+                .expect("A convex shape needs at least three points that are not on a line"),
+        };
+
+        // The mass is the one of the body, the collider does not add to it.
+        let collider = collider
+            .density(0.0)
+            .friction(spec.friction)
+            .restitution(spec.restitution)
+            .restitution_combine_rule(CoefficientCombineRule::Max)
+            .sensor(spec.sensor)
+            .collision_groups(self.matrix.groups(spec.layer))
+            .user_data(spec.data.into())
+            .active_events(ActiveEvents::CONTACT_EVENTS | ActiveEvents::INTERSECTION_EVENTS)
+            .active_hooks(if spec.one_way {
+                ActiveHooks::MODIFY_SOLVER_CONTACTS
+            } else {
+                ActiveHooks::empty()
+            })
+            .build();
+
+        self.collider_set
+            .insert_with_parent(collider, handle, &mut self.body_set);
+        self.query_outdated = true;
+
+        handle
+    }
+
+    /// Returns a immutable body from the handle provided by the above helper functions.
+    pub fn get_rigid_body(&mut self, handle: RigidBodyHandle) -> &RigidBody {
+        self.body_set.get(handle).expect("Body not found!")
+    }
+
+    /// Returns a mutable body from the handle provided by the above helper functions.
+    pub fn get_rigid_body_mut(&mut self, handle: RigidBodyHandle) -> &mut RigidBody {
+        self.body_set.get_mut(handle).expect("Body not found!")
+    }
+
+    /// Draw the views of the debug overlay that are switched on.
+    pub fn draw_debug(
+        &self,
+        ctx: &mut Context,
+        camera: &Camera,
+        overlay: &DebugOverlay,
+    ) -> GameResult {
+        let to_screen = |point: na::Point2<N>| {
+            let point = camera.calculate_dest_point(Vec2::new(point.x, point.y));
+
+            Point2::new(point.x, point.y)
+        };
+
+        let mut mesh = graphics::MeshBuilder::new();
+        // An empty mesh cannot be built.
+        let mut is_empty = true;
+
         for (_, collider) in self.collider_set.iter() {
-            let shape = collider.shape().aabb(collider.position());
-
-            let rect = graphics::Mesh::new_rectangle(
-                ctx,
-                graphics::DrawMode::Stroke(graphics::StrokeOptions::DEFAULT),
-                Rect::new(0.0, 0.0, shape.extents().x, shape.extents().y),
-                graphics::WHITE,
-            )?;
-
-            let pos = camera.calculate_dest_point(Vec2::new(shape.mins.x, shape.mins.y));
-
-            graphics::draw(
-                ctx,
-                &rect,
-                DrawParam::default()
-                    .dest(Point2::new(pos.x, pos.y))
-                    .offset(Point2::new(0.5, 0.5)),
-            )?;
+            let body = collider.parent();
+
+            // Draw the colliders with the bodies, between where they were and where they are.
+            let shift = body.map_or(Vector2::zeros(), |body| {
+                self.interpolated_position(body) - isometry_to_point(self.body_set[body].position())
+            });
+
+            let sleeping = body.is_some_and(|body| self.body_set[body].is_sleeping());
+
+            let outline = Self::outline(collider)
+                .into_iter()
+                .map(|point| to_screen(collider.position() * point + shift))
+                .collect::<Vec<_>>();
+
+            if outline.len() >= 3 {
+                if overlay.shows(DebugView::Shapes) {
+                    let color = match body.map(|body| self.body_set[body].body_type()) {
+                        _ if collider.is_sensor() => Color::from_rgb(240, 220, 60),
+                        Some(RigidBodyType::Dynamic) => Color::from_rgb(80, 230, 120),
+                        Some(RigidBodyType::Static) | None => Color::from_rgb(90, 160, 255),
+                        Some(_) => Color::from_rgb(255, 170, 60),
+                    };
+
+                    mesh.polygon(DrawMode::stroke(1.0), &outline, color)?;
+                    is_empty = false;
+                }
+
+                if overlay.shows(DebugView::Sleeping) && sleeping {
+                    mesh.polygon(DrawMode::fill(), &outline, Color::new(0.6, 0.6, 0.6, 0.5))?;
+                    is_empty = false;
+                }
+            }
+
+            if overlay.shows(DebugView::Bounds) {
+                let bounds = collider.compute_aabb();
+                let mins = to_screen(bounds.mins + shift);
+                let extents = bounds.extents();
+
+                mesh.rectangle(
+                    DrawMode::stroke(1.0),
+                    Rect::new(mins.x, mins.y, extents.x, extents.y),
+                    graphics::WHITE,
+                );
+                is_empty = false;
+            }
+        }
+
+        if overlay.shows(DebugView::Contacts) {
+            let red = Color::from_rgb(255, 80, 76);
+
+            for pair in self.narrow_phase.contact_pairs() {
+                for manifold in &pair.manifolds {
+                    for contact in &manifold.data.solver_contacts {
+                        let point = to_screen(contact.point);
+                        let normal = to_screen(contact.point + manifold.data.normal * 16.0);
+
+                        mesh.circle(DrawMode::fill(), point, 3.0, 0.5, red);
+                        mesh.line(&[point, normal], 1.0, red)?;
+                        is_empty = false;
+                    }
+                }
+            }
         }
-        Ok(())
+
+        if overlay.shows(DebugView::RayCasts) {
+            for ray in self.ray_casts.iter().flatten() {
+                if (ray.end - ray.origin).norm() >= 1.0 {
+                    let color = Color::from_rgb(80, 220, 240);
+
+                    mesh.line(&[to_screen(ray.origin), to_screen(ray.end)], 1.0, color)?;
+                    mesh.circle(DrawMode::fill(), to_screen(ray.end), 2.0, 0.5, color);
+                    is_empty = false;
+                }
+            }
+        }
+
+        if overlay.shows(DebugView::Velocities) {
+            for (handle, body) in self.body_set.iter() {
+                // Where the body goes in a quarter of a second.
+                let velocity = body.linvel() / 4.0;
+
+                if body.is_dynamic() && velocity.norm() >= 1.0 {
+                    let position = self.interpolated_position(handle);
+
+                    mesh.line(
+                        &[to_screen(position), to_screen(position + velocity)],
+                        1.0,
+                        Color::from_rgb(230, 90, 230),
+                    )?;
+                    is_empty = false;
+                }
+            }
+        }
+
+        if is_empty {
+            return Ok(());
+        }
+
+        let mesh = mesh.build(ctx)?;
+        graphics::draw(ctx, &mesh, DrawParam::default())
     }
 
-    /// Returns all of the collisions with the provided object
+    /// Returns all of the collisions with the provided object on one of the layers of the mask,
+    /// as the user data of the object and of what it touches along with the body it touches.
     pub fn collisions(
         &mut self,
-        object: DefaultBodyHandle,
-    ) -> Vec<(
-        (ObjectData, ObjectData),
-        DefaultBodyHandle,
-        &ContactManifold<f32>,
-    )> {
-        self.geometrical_world
-            .contacts_with(&self.collider_set, object, true)
-            .into_iter()
-            .flatten()
-            .map(|(handle1, _, handle2, _, _, manifold)| {
-                (
-                    (self.get_user_data(handle1), self.get_user_data(handle2)),
-                    handle2,
-                    manifold,
-                )
+        object: RigidBodyHandle,
+        mask: impl Into<LayerMask>,
+    ) -> Vec<((ObjectData, ObjectData), RigidBodyHandle)> {
+        let mask = mask.into();
+        let collider = self.collider(object);
+
+        self.narrow_phase
+            .contacts_with(collider)
+            .filter(|pair| pair.has_any_active_contact)
+            .filter_map(|pair| {
+                let other = if pair.collider1 == collider {
+                    pair.collider2
+                } else {
+                    pair.collider1
+                };
+
+                if !mask.contains(Self::layer_of(self.collider_set.get(other)?)) {
+                    return None;
+                }
+
+                Some((
+                    (self.collider_data(collider), self.collider_data(other)),
+                    self.collider_set.get(other)?.parent()?,
+                ))
             })
             .collect()
     }
 
-    /// Gets the user data of the 2 handles provided in the collisions function.
-    pub fn get_user_data(&self, object: DefaultBodyHandle) -> ObjectData {
-        let collider = self.collider_set.get(object).unwrap();
-
-        let data = *collider
-            .user_data()
-            .unwrap()
-            .downcast_ref::<ObjectData>()
-            .expect("Invalid types");
-
-        data
+    /// Gets the user data of the body provided, like the ones returned by the collisions function.
+    pub fn get_user_data(&self, object: RigidBodyHandle) -> ObjectData {
+        self.collider_data(self.collider(object))
     }
 
     /// Get the distance between a object
-    pub fn distance(&mut self, object1: DefaultBodyHandle, object2: DefaultBodyHandle) -> f32 {
-        let pos_1 = self.collider_set.get(object1).unwrap();
-        let pos_2 = self.collider_set.get(object2).unwrap();
-
-        ncollide2d::query::distance(
-            pos_1.position(),
-            pos_1.shape(),
-            pos_2.position(),
-            pos_2.shape(),
+    pub fn distance(&mut self, object1: RigidBodyHandle, object2: RigidBodyHandle) -> f32 {
+        let collider1 = &self.collider_set[self.collider(object1)];
+        let collider2 = &self.collider_set[self.collider(object2)];
+
+        parry::query::distance(
+            collider1.position(),
+            collider1.shape(),
+            collider2.position(),
+            collider2.shape(),
         )
+        .expect("Colliders are only made of supported shapes")
     }
 
-    /// Perform a raycast
+    /// Perform a raycast, hitting only the bodies on the layers of the mask
     pub fn ray_cast(
         &mut self,
         origin: na::Point2<f32>,
         dir: na::Vector2<f32>,
-    ) -> Vec<(
-        ObjectData,
-        &Collider<f32, DefaultBodyHandle>,
-        RayIntersection<f32>,
-    )> {
+        mask: impl Into<LayerMask>,
+    ) -> Vec<(ObjectData, RigidBodyHandle, RayIntersection)> {
+        let mask = mask.into();
         let ray = Ray::new(origin, dir);
+        let mut hits = vec![];
 
-        self.geometrical_world
-            .interferences_with_ray(
-                &self.collider_set,
-                &ray,
-                f32::MAX,
-                &CollisionGroups::default(),
-            )
-            .map(|(handle, collider, intersection)| {
-                (self.get_user_data(handle), collider, intersection)
-            })
-            .collect()
+        // A body created since the last step would be missed, and a destroyed one still hit.
+        if self.query_outdated {
+            self.update_query_pipeline();
+        }
+
+        self.query_pipeline.intersections_with_ray(
+            &self.collider_set,
+            &ray,
+            f32::MAX,
+            true,
+            InteractionGroups::all(),
+            None,
+            |handle, intersection| {
+                let collider = &self.collider_set[handle];
+
+                if !mask.contains(Self::layer_of(collider)) {
+                    return true;
+                }
+
+                if let Some(body) = collider.parent() {
+                    hits.push((ObjectData::from(collider.user_data), body, intersection));
+                }
+
+                true
+            },
+        );
+
+        if let Some(ray_casts) = &mut self.ray_casts {
+            let length = hits
+                .iter()
+                .map(|(_, _, hit)| hit.toi)
+                .fold(Self::RAY_CAST_LENGTH / dir.norm(), N::min);
+
+            ray_casts.push(RecordedRay {
+                origin,
+                end: ray.point_at(length),
+                shown: 0.0,
+            });
+        }
+
+        hits
+    }
+
+    /// Keep the rays cast from now on for the debug overlay to draw, or stop.
+    pub fn record_ray_casts(&mut self, record: bool) {
+        if record != self.ray_casts.is_some() {
+            self.ray_casts = if record { Some(vec![]) } else { None };
+        }
+    }
+
+    /// Where to draw the body, between where it was before the last step and where it is now, as
+    /// far as the time not yet stepped goes. Keeps the movement smooth when the world steps less
+    /// often than the frames are drawn.
+    pub fn interpolated_position(&self, body: RigidBodyHandle) -> na::Point2<N> {
+        let current = isometry_to_point(self.body_set[body].position());
+
+        match self.previous.get(&body) {
+            Some(previous) => previous + (current - previous) * (self.accumulator / Self::STEP),
+            None => current,
+        }
+    }
+
+    /// Move the body to the provided position right away, without it being drawn on the way there.
+    pub fn teleport(&mut self, body: RigidBodyHandle, position: na::Point2<N>) {
+        self.previous.remove(&body);
+        self.body_set[body].set_position(point_to_isometry(position), true);
+    }
+
+    /// Move a kinematic body to the provided position over the next step. What is in its way gets
+    /// pushed, unlike with [`Physics::teleport`].
+    pub fn move_kinematic(&mut self, body: RigidBodyHandle, position: na::Point2<N>) {
+        let body = &mut self.body_set[body];
+
+        body.set_next_kinematic_position(point_to_isometry(position));
+        body.wake_up(true);
+    }
+
+    /// Move a body by the provided offset along with what carries it, like a platform it stands
+    /// on. Called right after a step, so that it is drawn moving with its carrier.
+    pub fn carry(&mut self, body: RigidBodyHandle, offset: na::Vector2<N>) {
+        let body = &mut self.body_set[body];
+        let position = isometry_to_point(body.position()) + offset;
+
+        body.set_position(point_to_isometry(position), true);
     }
 
-    pub fn destroy_body(&mut self, handle: DefaultBodyHandle) {
-        self.body_set.remove(handle);
-        self.collider_set.remove(handle);
+    pub fn destroy_body(&mut self, handle: RigidBodyHandle) {
+        self.previous.remove(&handle);
+        self.body_set.remove(
+            handle,
+            &mut self.island_manager,
+            &mut self.collider_set,
+            &mut self.joint_set,
+        );
+        self.query_outdated = true;
+    }
+
+    /// The collider of a body. Every body is created with exactly one.
+    fn collider(&self, body: RigidBodyHandle) -> ColliderHandle {
+        self.body_set[body].colliders()[0]
+    }
+
+    /// The outline of the shape of a collider, relative to the collider.
+    fn outline(collider: &Collider) -> Vec<na::Point2<N>> {
+        let shape = collider.shape();
+
+        if let Some(cuboid) = shape.as_cuboid() {
+            cuboid.to_polyline()
+        } else if let Some(ball) = shape.as_ball() {
+            ball.to_polyline(16)
+        } else if let Some(capsule) = shape.as_capsule() {
+            capsule.to_polyline(8)
+        } else if let Some(convex) = shape.as_convex_polygon() {
+            convex.points().to_vec()
+        } else {
+            vec![]
+        }
+    }
+
+    /// The layer a collider was created on, which is its only membership.
+    fn layer_of(collider: &Collider) -> Layer {
+        let membership = collider.collision_groups().memberships;
+
+        Layer::ALL
+            .iter()
+            .copied()
+            .find(|&layer| LayerMask::from(layer).0 == membership)
+            .expect("Every collider is created on a single layer")
+    }
+
+    fn collider_data(&self, collider: ColliderHandle) -> ObjectData {
+        ObjectData::from(self.collider_set[collider].user_data)
+    }
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -0,0 +1,399 @@
+//! Steps the physics world of levels without a window, with the image sizes read from the files.
+
+use std::fs;
+
+use call_of_ferris::level::Level;
+use rapier2d::na;
+
+use crate::utils::AssetManager;
+
+use super::{
+    components::{
+        bullet::{Turbofish, WeaponType},
+        effects::Effects,
+        enemy::{EnemyBehaviour, EnemyBrain, EnemyState},
+        player::DamageSource,
+        tile::Tile,
+    },
+    map::{load_level, Map},
+    physics::{Layer, ObjectData, Physics},
+};
+
+const RESOURCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/resources");
+
+fn assets() -> AssetManager {
+    AssetManager::headless(RESOURCES).expect("Cannot read the resources")
+}
+
+/// Build the world of a level, with the player where the game puts it when the level starts.
+fn world(level: &Level, assets: &AssetManager) -> (Map, Physics) {
+    let mut physics = Map::physics(level);
+    let mut map = Map::build(level, &mut physics, assets);
+
+    map.player.init(&mut physics);
+
+    (map, physics)
+}
+
+/// Step the world for the provided number of seconds.
+fn run(physics: &mut Physics, seconds: f32) {
+    for _ in 0..(seconds / Physics::STEP) as u32 {
+        physics.step();
+    }
+}
+
+#[test]
+fn player_lands_on_the_first_tile() {
+    let assets = assets();
+    let level = Level::parse("4--", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    run(&mut physics, 2.0);
+
+    let (tile_type, tile) = level.tiles[0];
+    let (_, tile_height) = assets.image_size(Tile::look(tile_type).0);
+    let (_, player_height) = assets.image_size("Some(ferris).png");
+
+    let position = map.player.position(&mut physics);
+    let standing_y = tile.position.y - (tile_height + player_height) as f32 / 2.0;
+
+    assert!(
+        (position.x - tile.position.x).abs() <= tile_type.width() / 2.0,
+        "the player is at X {}, not above the first tile at X {}",
+        position.x,
+        tile.position.x
+    );
+    assert!(
+        (position.y - standing_y).abs() < 1.0,
+        "the player is at Y {}, not standing on the first tile at Y {}",
+        position.y,
+        standing_y
+    );
+
+    let velocity = physics.get_rigid_body(map.player.handle()).linvel().norm();
+    assert!(velocity < 1.0, "the player still moves at {}", velocity);
+}
+
+#[test]
+fn turbofish_hits_enemy_until_it_is_destroyed() {
+    let assets = assets();
+    let level = Level::parse("[-4---8--]", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    run(&mut physics, 1.0);
+
+    let health = map.enemies[0].health;
+    let shots = (health + Turbofish::DAMAGE - 1) / Turbofish::DAMAGE;
+
+    let mut effects = Effects::default();
+    let mut bullets = vec![];
+
+    for shot in 1..=shots {
+        let fish = map
+            .player
+            .shoot(&mut physics, &assets, &WeaponType::Turbofish)
+            .expect("the player starts with ammo");
+        map.player.weapons.push(fish);
+
+        let mut destroyed = false;
+
+        // Half a second is plenty for a bullet going 1000 pixels per second.
+        for _ in 0..30 {
+            physics.step();
+            let contacts = physics.drain_contacts();
+
+            destroyed = map.enemies[0].update(
+                &mut physics,
+                &assets,
+                &mut effects,
+                &mut map.player,
+                &mut bullets,
+                &contacts,
+            );
+
+            if map.player.weapons.is_empty() {
+                break;
+            }
+        }
+
+        assert!(
+            map.player.weapons.is_empty(),
+            "turbofish {} did not hit the enemy",
+            shot
+        );
+        assert_eq!(effects.damage_numbers.len(), shot as usize);
+
+        if shot < shots {
+            assert!(!destroyed, "the enemy was destroyed by turbofish {}", shot);
+            assert_eq!(map.enemies[0].health, health - shot * Turbofish::DAMAGE);
+        } else {
+            assert!(destroyed, "the enemy is still there after {} hits", shot);
+            assert_eq!(effects.shockwaves.len(), 1, "the enemy did not explode");
+        }
+    }
+}
+
+#[test]
+fn enemy_shoots_player_in_sight() {
+    let assets = assets();
+    let level = Level::parse("[-4---8-]", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    run(&mut physics, 1.0);
+
+    let health = map.player.health;
+    let mut effects = Effects::default();
+    let mut bullets = vec![];
+
+    let hit = (0..60).position(|_| {
+        physics.step();
+        let contacts = physics.drain_contacts();
+
+        map.enemies[0].update(
+            &mut physics,
+            &assets,
+            &mut effects,
+            &mut map.player,
+            &mut bullets,
+            &contacts,
+        );
+
+        // The same order as the game, the player is hurt before the bullet is gone.
+        map.player.touch(&mut physics, &contacts);
+        bullets.retain_mut(|bullet| !bullet.update(&mut physics, &contacts));
+
+        map.player.health < health
+    });
+
+    assert!(
+        hit.is_some(),
+        "the enemy did not hit the player in a second"
+    );
+    let gopher = assets.roster().kind("gopher").unwrap();
+    let damage = gopher.weapon.as_ref().unwrap().damage;
+
+    assert_eq!(map.player.health, health - damage);
+    assert!(bullets.is_empty(), "the bullet was not destroyed");
+}
+
+#[test]
+fn enemy_patrols_without_falling() {
+    let assets = assets();
+    let level = Level::parse(".enemy sight=0\n[-4-]_[--8--]", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    let spawn = level.enemies[0].2;
+    let (mut left, mut right) = (spawn.position.x, spawn.position.x);
+    let mut effects = Effects::default();
+    let mut bullets = vec![];
+
+    for _ in 0..600 {
+        physics.step();
+        let contacts = physics.drain_contacts();
+
+        map.enemies[0].update(
+            &mut physics,
+            &assets,
+            &mut effects,
+            &mut map.player,
+            &mut bullets,
+            &contacts,
+        );
+
+        let position = map.enemies[0].position(&mut physics);
+        left = left.min(position.x);
+        right = right.max(position.x);
+    }
+
+    let position = map.enemies[0].position(&mut physics);
+
+    assert!(
+        position.y < spawn.position.y + 10.0,
+        "the enemy fell off its platform to Y {}",
+        position.y
+    );
+    assert!(
+        left < spawn.position.x - 20.0 && right > spawn.position.x + 20.0,
+        "the enemy did not walk both ways, it stayed between X {} and {}",
+        left,
+        right
+    );
+}
+
+#[test]
+fn every_kind_of_enemy_spawns_from_its_glyph() {
+    let assets = assets();
+    let roster = assets.roster();
+
+    let glyphs = roster
+        .enemies
+        .iter()
+        .map(|kind| kind.glyph)
+        .collect::<String>();
+    let level = Level::parse(&format!("[-4-]_[-{}-]", glyphs), roster).unwrap();
+    let (map, _) = world(&level, &assets);
+
+    assert_eq!(map.enemies.len(), roster.enemies.len());
+
+    for (enemy, kind) in map.enemies.iter().zip(&roster.enemies) {
+        assert_eq!(enemy.kind().name, kind.name);
+        assert_eq!(enemy.health, kind.health);
+        assert!(assets.image_size(&kind.sprite).0 > 0);
+    }
+}
+
+#[test]
+fn enemy_brain_attacks_then_flees() {
+    let behaviour = EnemyBehaviour::default();
+    let mut brain = EnemyBrain::new(behaviour.clone());
+
+    assert_eq!(brain.think(0.0, false, 1.0), EnemyState::Patrol);
+    assert_eq!(brain.think(1.0, true, 1.0), EnemyState::Alert(1.0));
+    assert_eq!(
+        brain.think(1.0 + behaviour.alert, true, 1.0),
+        EnemyState::Attack
+    );
+
+    // It keeps looking for the player for a while, then patrols again.
+    assert_eq!(brain.think(2.0, false, 1.0), EnemyState::Attack);
+    assert_eq!(brain.think(10.0, false, 1.0), EnemyState::Patrol);
+
+    assert_eq!(
+        brain.think(11.0, true, behaviour.flee / 2.0),
+        EnemyState::Flee
+    );
+    assert_eq!(
+        brain.think(20.0, false, behaviour.flee / 2.0),
+        EnemyState::Flee
+    );
+}
+
+#[test]
+fn player_is_invulnerable_after_getting_hurt() {
+    let assets = assets();
+    let level = Level::parse("[-4--]", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    run(&mut physics, 1.0);
+
+    let health = map.player.health;
+    let from = map.player.position(&mut physics);
+
+    map.player.hurt(&mut physics, DamageSource::Spikes, from);
+    let knockback = *physics.get_rigid_body(map.player.handle()).linvel();
+
+    map.player.hurt(&mut physics, DamageSource::Spikes, from);
+    assert_eq!(
+        map.player.health,
+        health - DamageSource::Spikes.damage(),
+        "the player got hurt twice in a row"
+    );
+    assert_eq!(
+        *physics.get_rigid_body(map.player.handle()).linvel(),
+        knockback,
+        "the player got thrown while invulnerable"
+    );
+
+    run(&mut physics, 1.1);
+
+    map.player.hurt(&mut physics, DamageSource::Spikes, from);
+    assert_eq!(
+        map.player.health,
+        health - 2 * DamageSource::Spikes.damage(),
+        "the player is still invulnerable"
+    );
+}
+
+#[test]
+fn player_on_spikes_is_hurt_again_after_being_invulnerable() {
+    let assets = assets();
+    let level = Level::parse("[-4-]__[^^^^^]", assets.roster()).unwrap();
+    let (mut map, mut physics) = world(&level, &assets);
+
+    run(&mut physics, 1.0);
+
+    let health = map.player.health;
+    let from = map.player.position(&mut physics);
+    map.player.hurt(&mut physics, DamageSource::Enemy, from);
+
+    // Drop the invulnerable player on the spikes, where it stays.
+    let (_, spikes) = level.tiles[level.tiles.len() - 3];
+    map.player
+        .set_position(&mut physics, spikes.position - na::Vector2::new(0.0, 100.0));
+    physics
+        .get_rigid_body_mut(map.player.handle())
+        .set_linvel(na::Vector2::zeros(), true);
+
+    let mut step = |physics: &mut Physics, seconds: f32| {
+        for _ in 0..(seconds / Physics::STEP) as u32 {
+            physics.step();
+            let contacts = physics.drain_contacts();
+
+            map.player.touch(physics, &contacts);
+        }
+
+        map.player.health
+    };
+
+    assert_eq!(
+        step(&mut physics, 0.5),
+        health - DamageSource::Enemy.damage(),
+        "the spikes hurt the invulnerable player"
+    );
+    assert_eq!(
+        step(&mut physics, 0.6),
+        health - DamageSource::Enemy.damage() - DamageSource::Spikes.damage(),
+        "the spikes did not hurt the player once it was no longer invulnerable"
+    );
+}
+
+#[test]
+fn ray_casts_see_bodies_right_after_they_change() {
+    let assets = assets();
+    let level = Level::parse("[-4-]", assets.roster()).unwrap();
+    let (_, mut physics) = world(&level, &assets);
+
+    // Straight down onto the first tile, before the world was ever stepped.
+    let (_, tile) = level.tiles[0];
+    let above = tile.position - na::Vector2::new(0.0, 200.0);
+    let down = na::Vector2::new(0.0, 1.0);
+
+    let hits = physics.ray_cast(above, down, Layer::Ground);
+    let (data, ground, _) = hits.first().expect("the ray did not hit the new tile");
+    assert_eq!(*data, ObjectData::Ground);
+
+    physics.destroy_body(*ground);
+
+    let hits = physics.ray_cast(above, down, Layer::Ground);
+    assert!(hits.is_empty(), "the ray hit the destroyed tile");
+}
+
+#[test]
+fn every_map_starts_on_the_ground() {
+    let assets = assets();
+
+    for map in fs::read_dir(format!("{}/maps", RESOURCES)).unwrap() {
+        let path = map.unwrap().path();
+
+        if path.extension().is_none_or(|extension| extension != "map") {
+            continue;
+        }
+
+        let map_id = path.file_stem().unwrap().to_string_lossy();
+        let level = load_level(&map_id, &assets).unwrap();
+        let (mut map, mut physics) = world(&level, &assets);
+
+        run(&mut physics, 3.0);
+
+        let position = map.player.position(&mut physics);
+        let velocity = physics.get_rigid_body(map.player.handle()).linvel().norm();
+
+        assert!(
+            position.y < map.death_y && velocity < 1.0,
+            "the player of {} did not land, it is at {} going {}",
+            map_id,
+            position,
+            velocity
+        );
+    }
+}
@@ -60,12 +60,20 @@ impl Menu {
             .font(consolas)
             .scale(Scale::uniform(15.0));
 
+        let press_paren_to = TextFragment::new("Press ( to")
+            .font(consolas)
+            .scale(Scale::uniform(15.0));
+
+        let press_rparen_to = TextFragment::new("Press ) to")
+            .font(consolas)
+            .scale(Scale::uniform(15.0));
+
         graphics::draw(
             ctx,
             &Text::new(press_and_to),
             DrawParam::default().dest(Point2::new(
                 width - 200.0,
-                height - (ferris_ninja.height() + 30) as f32,
+                height - (ferris_ninja.height() + 110) as f32,
             )),
         )?;
 
@@ -74,7 +82,7 @@ impl Menu {
             graphics::DrawMode::fill(),
             graphics::Rect::new(
                 width - 200.0,
-                height - (ferris_ninja.height() + 10) as f32,
+                height - (ferris_ninja.height() + 90) as f32,
                 220.0,
                 40.0,
             ),
@@ -82,6 +90,18 @@ impl Menu {
         )?;
 
         let quit_rect = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                width - 200.0,
+                height - (ferris_ninja.height() + 10) as f32,
+                220.0,
+                40.0,
+            ),
+            Color::from_rgba(36, 36, 36, 128),
+        )?;
+
+        let editor_rect = graphics::Mesh::new_rectangle(
             ctx,
             graphics::DrawMode::fill(),
             graphics::Rect::new(
@@ -93,6 +113,18 @@ impl Menu {
             Color::from_rgba(36, 36, 36, 128),
         )?;
 
+        let endless_rect = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(
+                width - 200.0,
+                height - (ferris_ninja.height() - 150) as f32,
+                220.0,
+                40.0,
+            ),
+            Color::from_rgba(36, 36, 36, 128),
+        )?;
+
         let play_text = TextFragment::new("PLAY")
             .font(consolas)
             .scale(Scale::uniform(20.0));
@@ -101,15 +133,25 @@ impl Menu {
             .font(consolas)
             .scale(Scale::uniform(20.0));
 
+        let editor_text = TextFragment::new("EDITOR")
+            .font(consolas)
+            .scale(Scale::uniform(20.0));
+
+        let endless_text = TextFragment::new("ENDLESS")
+            .font(consolas)
+            .scale(Scale::uniform(20.0));
+
         graphics::draw(ctx, &play_rect, DrawParam::default())?;
         graphics::draw(ctx, &quit_rect, DrawParam::default())?;
+        graphics::draw(ctx, &editor_rect, DrawParam::default())?;
+        graphics::draw(ctx, &endless_rect, DrawParam::default())?;
 
         graphics::draw(
             ctx,
             &Text::new(play_text),
             DrawParam::default().dest(Point2::new(
                 width - 170.0,
-                height - ferris_ninja.height() as f32,
+                height - (ferris_ninja.height() + 80) as f32,
             )),
         )?;
 
@@ -118,19 +160,55 @@ impl Menu {
             &Text::new(press_pointer_to),
             DrawParam::default().dest(Point2::new(
                 width - 200.0,
-                height - (ferris_ninja.height() - 50) as f32,
+                height - (ferris_ninja.height() + 30) as f32,
             )),
         )?;
 
         graphics::draw(
             ctx,
             &Text::new(quit_text),
+            DrawParam::default().dest(Point2::new(
+                width - 170.0,
+                height - ferris_ninja.height() as f32,
+            )),
+        )?;
+
+        graphics::draw(
+            ctx,
+            &Text::new(press_paren_to),
+            DrawParam::default().dest(Point2::new(
+                width - 200.0,
+                height - (ferris_ninja.height() - 50) as f32,
+            )),
+        )?;
+
+        graphics::draw(
+            ctx,
+            &Text::new(editor_text),
             DrawParam::default().dest(Point2::new(
                 width - 170.0,
                 height - (ferris_ninja.height() - 80) as f32,
             )),
         )?;
 
+        graphics::draw(
+            ctx,
+            &Text::new(press_rparen_to),
+            DrawParam::default().dest(Point2::new(
+                width - 200.0,
+                height - (ferris_ninja.height() - 130) as f32,
+            )),
+        )?;
+
+        graphics::draw(
+            ctx,
+            &Text::new(endless_text),
+            DrawParam::default().dest(Point2::new(
+                width - 170.0,
+                height - (ferris_ninja.height() - 160) as f32,
+            )),
+        )?;
+
         graphics::present(ctx)
     }
 
@@ -143,6 +221,10 @@ impl Menu {
             return Some(Screen::Play);
         } else if keycode == KeyCode::Key8 {
             exit(0);
+        } else if keycode == KeyCode::Key9 {
+            return Some(Screen::Editor);
+        } else if keycode == KeyCode::Key0 {
+            return Some(Screen::Endless);
         }
 
         None
@@ -1,3 +1,4 @@
 pub mod dead;
+pub mod editor;
 pub mod game;
 pub mod menu;
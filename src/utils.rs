@@ -1,23 +1,30 @@
-use std::{borrow::Cow, collections::HashMap, error::Error, io::Read, sync::Mutex};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
 
 use ggez::{
-    audio::Source,
+    audio::{SoundSource, Source},
     graphics::{self, Color, DrawMode, Font, Image, Mesh},
     nalgebra::Point2,
-    timer, Context, GameResult,
+    timer, Context, GameError, GameResult,
 };
 
 use ggez_goodies::{camera::Camera, nalgebra_glm::Vec2};
 use graphics::DrawParam;
-use nphysics2d::{
-    algebra::Velocity2,
-    nalgebra as na,
-    ncollide2d::shape::{Ball, ShapeHandle},
-    object::{BodyPartHandle, ColliderDesc, DefaultBodyHandle, RigidBodyDesc},
-};
 use rand::Rng;
+use rapier2d::{dynamics::RigidBodyHandle, na};
+
+use call_of_ferris::level::Roster;
 
-use crate::game::physics::{isometry_to_point, point_to_isometry, ObjectData, Physics};
+use crate::game::physics::{BodySpec, ObjectData, Physics, Shape};
 
 pub type FerrisResult<T> = Result<T, Box<dyn Error>>;
 
@@ -46,28 +53,66 @@ enum Asset {
     Image(Image),
     Font(Font),
     Audio(Mutex<Source>),
-    File(String),
 }
 
 pub struct AssetManager {
     assets: HashMap<String, Asset>,
+    /// The width and the height of the images, which is all the physics world needs of them.
+    sizes: HashMap<String, (u16, u16)>,
+    /// Text files are kept separately so that they can be reloaded while the game is running.
+    files: RefCell<HashMap<String, String>>,
+    /// The kinds of enemies, from `enemies.toml`.
+    roster: Roster,
 }
 
 impl AssetManager {
     pub fn new() -> Self {
         Self {
             assets: HashMap::new(),
+            sizes: HashMap::new(),
+            files: RefCell::new(HashMap::new()),
+            roster: Roster::default(),
+        }
+    }
+
+    /// Load what the game world needs without a window: the sizes of the images, read from the
+    /// headers of the files in `images`, and the maps in `maps`. Nothing can be drawn or
+    /// played with it, it is meant for running the physics in the tests.
+    pub fn headless(resources: impl AsRef<Path>) -> io::Result<Self> {
+        let resources = resources.as_ref();
+        let mut asset_manager = Self::new();
+
+        for image in fs::read_dir(resources.join("images"))? {
+            let image = image?;
+            let name = image.file_name().to_string_lossy().to_string();
+
+            let size = read_image_size(&image.path())?;
+            asset_manager.sizes.insert(name, size);
+        }
+
+        for map in fs::read_dir(resources.join("maps"))? {
+            let map = map?;
+            let path = format!("/maps/{}", map.file_name().to_string_lossy());
+
+            let source = fs::read_to_string(map.path())?;
+            asset_manager.files.borrow_mut().insert(path, source);
         }
+
+        let roster = fs::read_to_string(resources.join("enemies.toml"))?;
+        asset_manager.roster =
+            Roster::parse(&roster).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(asset_manager)
     }
 
     pub fn load_image(&mut self, ctx: &mut Context, filename: Cow<'_, str>) {
-        self.assets.insert(
-            filename.to_string(),
-            Asset::Image(
-                Image::new(ctx, format!("/images/{}", filename))
-                    .unwrap_or_else(|_| panic!("Cannot load {}", filename)),
-            ),
-        );
+        let image = Image::new(ctx, format!("/images/{}", filename))
+            .unwrap_or_else(|_| panic!("Cannot load {}", filename));
+
+        self.sizes
+            .insert(filename.to_string(), (image.width(), image.height()));
+        self.assets
+            .insert(filename.to_string(), Asset::Image(image));
     }
 
     pub fn load_font(&mut self, ctx: &mut Context, filename: Cow<'_, str>) {
@@ -91,58 +136,202 @@ impl AssetManager {
     }
 
     pub fn load_file(&mut self, ctx: &mut Context, folder: &str, filename: Cow<'_, str>) {
+        self.reload_file(ctx, folder, &filename)
+            .unwrap_or_else(|_| panic!("Cannot load {}", filename));
+    }
+
+    /// Read a text file from the disk again. Unlike [`AssetManager::load_file`] this can be
+    /// done while the asset manager is shared.
+    pub fn reload_file(&self, ctx: &mut Context, folder: &str, filename: &str) -> GameResult<()> {
         let path = format!("/{}/{}", folder, filename);
 
-        let mut file = ggez::filesystem::open(ctx, &path).unwrap();
+        let mut file = ggez::filesystem::open(ctx, &path)?;
+        let mut buffer = String::new();
+
+        file.read_to_string(&mut buffer)?;
+
+        self.files.borrow_mut().insert(path, buffer);
+
+        Ok(())
+    }
+
+    /// Read the kinds of enemies from `enemies.toml`.
+    pub fn load_roster(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let mut file = ggez::filesystem::open(ctx, "/enemies.toml")?;
         let mut buffer = String::new();
 
-        file.read_to_string(&mut buffer).unwrap();
+        file.read_to_string(&mut buffer)?;
+
+        self.roster = Roster::parse(&buffer)
+            .map_err(|e| GameError::ResourceLoadError(format!("/enemies.toml: {}", e)))?;
+
+        Ok(())
+    }
 
-        self.assets.insert(path, Asset::File(buffer));
+    pub fn roster(&self) -> &Roster {
+        &self.roster
     }
 
     pub fn get_image(&self, filename: &str) -> Image {
-        match self.assets.get(&filename.to_string()).unwrap() {
+        match self.assets.get(filename).unwrap() {
             Asset::Image(image) => image.to_owned(),
             _ => panic!(),
         }
     }
 
+    /// The width and the height of an image, which unlike the image itself are there without a
+    /// window too.
+    pub fn image_size(&self, filename: &str) -> (u16, u16) {
+        *self
+            .sizes
+            .get(filename)
+            .unwrap_or_else(|| panic!("Cannot find {}", filename))
+    }
+
     pub fn get_font(&self, filename: &str) -> Font {
-        match self.assets.get(&filename.to_string()).unwrap() {
+        match self.assets.get(filename).unwrap() {
             Asset::Font(font) => font.to_owned(),
             _ => panic!(),
         }
     }
 
     pub fn get_sound(&self, filename: &str) -> &Mutex<Source> {
-        match self.assets.get(&filename.to_string()).unwrap() {
+        match self.assets.get(filename).unwrap() {
             Asset::Audio(audio) => audio,
             _ => panic!(),
         }
     }
 
+    /// Play a sound from the start. Does nothing if the sounds were not loaded, like without a
+    /// window.
+    pub fn play_sound(&self, filename: &str) {
+        if self.has_sound(filename) {
+            play!(self.get_sound(filename));
+        }
+    }
+
     pub fn get_file(&self, filename: &str) -> String {
-        match self.assets.get(&filename.to_string()).unwrap() {
-            Asset::File(file) => file.to_owned(),
-            _ => panic!(),
+        self.files.borrow().get(filename).unwrap().to_owned()
+    }
+
+    pub fn has_image(&self, filename: &str) -> bool {
+        matches!(self.assets.get(filename), Some(Asset::Image(_)))
+    }
+
+    pub fn has_sound(&self, filename: &str) -> bool {
+        matches!(self.assets.get(filename), Some(Asset::Audio(_)))
+    }
+
+    pub fn has_file(&self, filename: &str) -> bool {
+        self.files.borrow().contains_key(filename)
+    }
+}
+
+/// The width and the height of a PNG or JPEG image, from its header.
+fn read_image_size(path: &Path) -> io::Result<(u16, u16)> {
+    let bytes = fs::read(path)?;
+
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not a PNG or a JPEG image", path.display()),
+        )
+    };
+
+    let number = |at: usize, length: usize| -> io::Result<u32> {
+        let bytes = bytes.get(at..at + length).ok_or_else(invalid)?;
+
+        Ok(bytes
+            .iter()
+            .fold(0, |number, &byte| (number << 8) | byte as u32))
+    };
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // The IHDR chunk comes first, with the width and the height.
+        return Ok((number(16, 4)? as u16, number(20, 4)? as u16));
+    }
+
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err(invalid());
+    }
+
+    // Go through the segments of the JPEG until the start of the frame, which has the size.
+    let mut at = 2;
+
+    loop {
+        let marker = number(at, 2)?;
+
+        match marker {
+            0xFFC0..=0xFFCF if ![0xFFC4, 0xFFC8, 0xFFCC].contains(&marker) => {
+                return Ok((number(at + 7, 2)? as u16, number(at + 5, 2)? as u16));
+            }
+            _ => at += 2 + number(at + 2, 2)? as usize,
         }
     }
 }
 
+/// Watches the files in a directory for changes by polling their modification time.
+pub struct FileWatcher {
+    dir: PathBuf,
+    modified: HashMap<String, SystemTime>,
+}
+
+impl FileWatcher {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let mut watcher = Self {
+            dir: dir.into(),
+            modified: HashMap::new(),
+        };
+
+        // Take the first snapshot, everything in the directory has just been loaded.
+        watcher.poll();
+        watcher
+    }
+
+    /// Returns the names of the files that were added or changed since the last poll.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut changed = vec![];
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return changed,
+        };
+
+        for entry in entries.flatten() {
+            let modified = match entry.metadata().and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            };
+
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if self.modified.insert(name.clone(), modified) != Some(modified) {
+                changed.push(name);
+            }
+        }
+
+        changed
+    }
+}
+
 pub struct ParticleSystem {
-    particles: Vec<DefaultBodyHandle>,
+    particles: Vec<RigidBodyHandle>,
     lifetime: f32,
 }
 
 impl ParticleSystem {
     const PARTICLE_JUICE: f32 = 300.0;
+    /// The colors of an explosion, from red to yellow.
+    pub const FIRE: [[u8; 3]; 2] = [[255, 0, 0], [255, 255, 0]];
 
+    /// Blow up into the provided amount of particles, in the provided area, each with a color
+    /// between the two provided ones.
     pub fn new(
         physics: &mut Physics,
         amount: usize,
         min: na::Point2<f32>,
         max: na::Point2<f32>,
+        colors: [[u8; 3]; 2],
     ) -> Self {
         let rng = &mut rand::thread_rng();
         let mut particles = vec![];
@@ -150,25 +339,29 @@ impl ParticleSystem {
         for _ in 0..amount {
             let position =
                 na::Point2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
-            let mut body = RigidBodyDesc::new()
+            let mix = rng.gen_range(0.0..=1.0);
+            let [r, g, b] = [0, 1, 2].map(|channel| {
+                lerp(colors[0][channel] as f32, colors[1][channel] as f32, mix) as u8
+            });
+
+            let handle = physics.create_body(
+                BodySpec::dynamic(
+                    ObjectData::Particle(Color::from_rgb(r, g, b)),
+                    Shape::Ball { radius: 2.0 },
+                )
+                .position(position)
                 .mass(0.1)
-                .position(point_to_isometry(position))
-                .build();
-
-            body.set_velocity(Velocity2::linear(
-                rng.gen_range(-Self::PARTICLE_JUICE..=Self::PARTICLE_JUICE),
-                rng.gen_range(-Self::PARTICLE_JUICE..=Self::PARTICLE_JUICE),
-            ));
-
-            let g = rng.gen_range(0..=255);
-
-            let handle = physics.create_rigid_body(body);
-            let shape = ShapeHandle::new(Ball::new(2.0));
-            let collider = ColliderDesc::new(shape)
-                .user_data(ObjectData::Particle(Color::from_rgb(255, g, 0)))
-                .build(BodyPartHandle(handle, 0));
-
-            physics.create_collider(collider);
+                .friction(0.5)
+                .rotates(true),
+            );
+
+            physics.get_rigid_body_mut(handle).set_linvel(
+                na::Vector2::new(
+                    rng.gen_range(-Self::PARTICLE_JUICE..=Self::PARTICLE_JUICE),
+                    rng.gen_range(-Self::PARTICLE_JUICE..=Self::PARTICLE_JUICE),
+                ),
+                true,
+            );
 
             particles.push(handle);
         }
@@ -186,8 +379,7 @@ impl ParticleSystem {
         camera: &mut Camera,
     ) -> GameResult<()> {
         for particle in &self.particles {
-            let body = physics.get_rigid_body(*particle);
-            let position = isometry_to_point(body.position());
+            let position = physics.interpolated_position(*particle);
             let color = physics.get_user_data(*particle).get_particle_data();
 
             let color = Color::new(color.r, color.g, color.b, self.lifetime);